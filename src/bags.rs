@@ -0,0 +1,95 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Shopping bags hold the items bought at the store by their IDs. Removing or
+// renumbering items would leave bags pointing at items that are gone or at
+// the wrong ones, so with --shopping-bags the contents are updated after
+// every clean. Without it the items in the bags are protected instead. Bags
+// that still hold something are never removed (see 'rules::ShoppingBagRule').
+//
+// The contents are assumed to be stored in a "Contents" entry of the bag,
+// i.e. "shoppingbagx3Contents", holding a list of item IDs:
+//     5 bytes   the ES2 header of the list, kept as it is
+//     u32       the number of items
+//     per item  the length of the ID as u8 and the ID, i.e. "sausagesx12"
+// This hasn't been checked against a save with filled bags yet, that's why
+// the contents are only changed if asked to. Entries that don't fit the
+// layout are never changed and their bags are treated like any other item.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Entry, display_tag, get_item_id, get_u32_le, parse_tag};
+
+
+
+pub const BAG_BASE: &[u8] = b"shoppingbagx";
+pub const CONTENTS_PROPERTY: &[u8] = b"Contents";
+
+
+
+// Reads the item IDs from the data of a Contents entry, None if it doesn't
+// fit the layout
+pub fn decode_contents(data: &[u8]) -> Option<Vec<Vec<u8>>> {
+    if data.len() < 9 {
+        return None;
+    }
+    let mut idx = 5;
+    let count = get_u32_le(data, &mut idx) as usize;
+    let mut res: Vec<Vec<u8>> = Vec::new();
+    for _ in 0..count {
+        let len = *data.get(idx)? as usize;
+        res.push(data.get(idx + 1..idx + 1 + len)?.to_vec());
+        idx += 1 + len;
+    }
+    if idx == data.len() { Some(res) } else { None }
+}
+
+
+
+// Builds the data of a Contents entry, the header is taken from the old data.
+// Fails if an ID doesn't fit its length byte.
+pub fn encode_contents(header: &[u8], ids: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    let mut res = header[..5].to_vec();
+    res.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+    for id in ids {
+        let len = u8::try_from(id.len()).map_err(|_| format!("the ID \"{}\" is longer than 255 bytes", display_tag(id)))?;
+        res.push(len);
+        res.extend_from_slice(id);
+    }
+    Ok(res)
+}
+
+
+
+// Checks whether an entry holds the contents of a shopping bag
+pub fn is_contents(e: &Entry) -> bool {
+    let parts = parse_tag(&e.tag);
+    parts.base == BAG_BASE && parts.property == CONTENTS_PROPERTY
+}
+
+
+
+// Updates the contents of every bag after cleaning: removed items are taken
+// out and renumbered items get their new ID. Returns warnings about the bags
+// that couldn't be updated.
+pub fn update_contents(entries: &mut [Entry], removed: &[Vec<u8>], renamed: &[(Vec<u8>, Vec<u8>)]) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    let removed: HashSet<&[u8]> = removed.iter().map(Vec::as_slice).collect();
+    let renamed: HashMap<&[u8], &[u8]> = renamed.iter().map(|(old, new)| (old.as_slice(), new.as_slice())).collect();
+    for e in entries.iter_mut().filter(|e| is_contents(e)) {
+        let Some(ids) = decode_contents(&e.data) else { continue };
+        let new_ids: Vec<Vec<u8>> = ids.iter()
+            .filter(|id| !removed.contains(id.as_slice()))
+            .map(|id| renamed.get(id.as_slice()).map_or_else(|| id.clone(), |new| new.to_vec()))
+            .collect();
+        if new_ids == ids {
+            continue;
+        }
+        match encode_contents(&e.data, &new_ids) {
+            Ok(data) => e.data = data,
+            Err(msg) => warnings.push(format!("the contents of {} weren't updated, {}", display_tag(&get_item_id(&e.tag)), msg))
+        }
+    }
+    warnings
+}
@@ -0,0 +1,109 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// The items of a fresh save game, taken from its items.txt. 'DEFAULT_ITEMS'
+// was put together by hand, with a baseline every item the game starts with
+// is protected when cleaning, and the items a played save lost can be found.
+//
+// The baseline is stored next to the save as "items_baseline.txt", an
+// unchanged copy of the fresh items.txt.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    Entry, display_name, display_tag, exit, exit_on_error, flag_value, generate_entries, get_item_id,
+    is_counter_tag, looks_like_counter, read_items_file, save_entries, savedir
+};
+
+
+
+const BASELINE_FILE: &str = "items_baseline.txt";
+
+
+
+// The IDs of the items of a fresh save, counters aren't items
+pub fn default_ids(entries: &[Entry]) -> Vec<Vec<u8>> {
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    entries.iter()
+        .filter(|e| !is_counter_tag(&e.tag) && !looks_like_counter(e))
+        .map(|e| get_item_id(&e.tag))
+        .filter(|id| seen.insert(id.clone()))
+        .collect()
+}
+
+
+
+// The IDs of the default items in the given directory's baseline, empty if
+// there is none
+pub fn load(dir: &Path) -> Vec<Vec<u8>> {
+    let path = dir.join(BASELINE_FILE);
+    if !path.is_file() {
+        return Vec::new();
+    }
+    let contents = exit_on_error(std::fs::read(&path), format!("Failed to read \"{}\"", path.display()).as_str());
+    default_ids(&generate_entries(&contents))
+}
+
+
+
+// The default items that aren't in the save any more
+pub fn lost(defaults: &[Vec<u8>], entries: &[Entry]) -> Vec<Vec<u8>> {
+    let present: HashSet<Vec<u8>> = entries.iter().map(|e| get_item_id(&e.tag)).collect();
+    defaults.iter().filter(|id| !present.contains(*id)).cloned().collect()
+}
+
+
+
+// Lists the default items a save lost
+fn format_lost(defaults: &[Vec<u8>], entries: &[Entry]) -> String {
+    let lost = lost(defaults, entries);
+    let mut out = format!("{} default items, {} of them are missing from the save\n", defaults.len(), lost.len());
+    for id in lost {
+        out.push_str(format!("  {} ({})\n", display_tag(&id), display_name(&id)).as_str());
+    }
+    out
+}
+
+
+
+// The "baseline" command: stores the items.txt of a fresh game as the
+// baseline of the save, or without a file tells which defaults the save lost
+pub fn run(args: &[String]) {
+    let usage = "Usage: baseline [<items.txt of a fresh game>] [--dir DIR]";
+    let mut fresh: Option<&String> = None;
+    let mut dir: Option<PathBuf> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => dir = Some(flag_value(arg, args.next())),
+            _ if fresh.is_none() && !arg.starts_with("--") => fresh = Some(arg),
+            _ => exit(format!("Unknown argument \"{}\"\n{}", arg, usage).as_str())
+        }
+    }
+    let dir = savedir::choose(dir.as_ref());
+
+    let defaults = match fresh {
+        Some(fresh) => {
+            let contents = exit_on_error(std::fs::read(fresh), format!("Failed to read \"{}\"", fresh).as_str());
+            let entries = generate_entries(&contents);
+            let defaults = default_ids(&entries);
+            if defaults.is_empty() {
+                exit(format!("\"{}\" doesn't contain any items", fresh).as_str());
+            }
+            save_entries(&dir.join(BASELINE_FILE), &entries);
+            println!("Stored {} default items, they're protected when cleaning", defaults.len());
+            defaults
+        },
+        None => {
+            let defaults = load(&dir);
+            if defaults.is_empty() {
+                exit(format!("There's no baseline yet\n{}", usage).as_str());
+            }
+            defaults
+        }
+    };
+    print!("{}", format_lost(&defaults, &generate_entries(&read_items_file(&dir))));
+}
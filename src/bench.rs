@@ -0,0 +1,130 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Timings for reading, cleaning and writing a large synthetic save, compared
+// to the byte-by-byte parser and the piecewise writer this program used to have
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::generator::{GeneratorOptions, generate};
+use crate::rules::{CleanConfig, build_rules};
+use crate::{Entry, EntryParser, EntryRef, clean_entries, generate_entries, mk_u32_le, write_entries};
+
+
+
+// Builds an items.txt with the given amount of instances per item group, a
+// quarter of them in the landfill
+fn synthetic_save(instances: usize) -> Vec<u8> {
+    let options = GeneratorOptions { instances, landfill: 25, ..GeneratorOptions::default() };
+    let mut res = Vec::new();
+    write_entries(&mut res, &generate(&options)).unwrap();
+    res
+}
+
+
+
+// The parser as it used to be, copying every payload byte by byte
+fn parse_bytewise(buf: &[u8]) -> Vec<Entry> {
+    let mut result = Vec::new();
+    let mut i: usize = 0;
+    while i < buf.len() {
+        let tag_length = buf[i + 1] as usize;
+        i += 2;
+        let mut e = Entry { tag: Vec::new(), data: Vec::new() };
+        for j in 0..tag_length {
+            e.tag.push(buf[i + j]);
+        }
+        i += tag_length;
+        let data_length = u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]) as usize;
+        i += 4;
+        for j in 0..data_length - 1 {
+            e.data.push(buf[i + j]);
+        }
+        i += data_length;
+        result.push(e);
+    }
+    result
+}
+
+
+
+// The writer as it used to be: six 'write' calls per entry, whose short
+// writes were never checked, and a new Vec for every data length
+#[allow(clippy::unused_io_amount)]
+fn write_piecewise<W: Write>(writer: &mut W, entries: &[Entry]) -> std::io::Result<()> {
+    for e in entries {
+        writer.write(&[0x7E])?;
+        writer.write(&[e.tag.len() as u8])?;
+        writer.write(&e.tag)?;
+        writer.write(&mk_u32_le(&(e.data.len() + 1)))?;
+        writer.write(&e.data)?;
+        writer.write(&[0x7B])?;
+    }
+    Ok(())
+}
+
+
+
+// Runs 'f' a few times and returns the fastest run
+fn fastest<T>(mut f: impl FnMut() -> T) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+
+
+// Prints one line of the benchmark results
+fn report(name: &str, time: Duration, baseline: Duration) {
+    println!(
+        "{:<28}{:>10.2} ms{:>8.1}x",
+        name,
+        time.as_secs_f64() * 1000.0,
+        baseline.as_secs_f64() / time.as_secs_f64()
+    );
+}
+
+
+
+pub fn run(instances: Option<String>) {
+    let instances: usize = instances.and_then(|n| n.parse().ok()).unwrap_or(2_500);
+    let save = synthetic_save(instances);
+    println!(
+        "Synthetic save: {} instances per group, {} entries, {:.1} MB",
+        instances,
+        EntryParser::new(&save).count(),
+        save.len() as f64 / 1_000_000.0
+    );
+
+    let bytewise = fastest(|| parse_bytewise(&save));
+    report("parse, byte by byte", bytewise, bytewise);
+    report("parse, borrowed", fastest(|| EntryParser::new(&save).collect::<Vec<EntryRef>>()), bytewise);
+    report("parse, owned", fastest(|| EntryParser::new(&save).map(EntryRef::to_entry).collect::<Vec<Entry>>()), bytewise);
+
+    let entries: Vec<Entry> = EntryParser::new(&save).map(EntryRef::to_entry).collect();
+    let piecewise = fastest(|| write_piecewise(&mut std::io::BufWriter::new(std::io::sink()), &entries));
+    report("write, piecewise", piecewise, piecewise);
+    report("write, one call per entry", fastest(|| write_entries(&mut std::io::BufWriter::new(std::io::sink()), &entries)), piecewise);
+
+    // Cleaning should scale linearly, so ten times the entries should take
+    // about ten times as long
+    let small = synthetic_save(90);
+    let large = synthetic_save(900);
+    let rules = build_rules(&CleanConfig::default());
+    let clean_small = fastest(|| clean_entries(generate_entries(&small), &rules).0);
+    let clean_large = fastest(|| clean_entries(generate_entries(&large), &rules).0);
+    println!("{:<28}{:>10.2} ms", format!("clean, {} entries", EntryParser::new(&small).count()), clean_small.as_secs_f64() * 1000.0);
+    println!(
+        "{:<28}{:>10.2} ms{:>8.1}x slower",
+        format!("clean, {} entries", EntryParser::new(&large).count()),
+        clean_large.as_secs_f64() * 1000.0,
+        clean_large.as_secs_f64() / clean_small.as_secs_f64()
+    );
+}
@@ -0,0 +1,125 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// The command line options for cleaning a save
+
+use std::path::PathBuf;
+
+use crate::{exit, flag_value};
+use crate::game::IfRunning;
+use crate::world::WorldAction;
+use crate::rules::CleanConfig;
+
+
+
+const USAGE: &str = "\
+Usage: msc_items_cleaner [options]
+       msc_items_cleaner explain <tag> [options]
+       msc_items_cleaner watch [options]
+       msc_items_cleaner reinsert [<item>...] [--dir DIR] [--wait|--force]
+       msc_items_cleaner spawn <base> --at x,y,z [--count N] [--dir DIR] [--wait|--force]
+       msc_items_cleaner move [<item>...] [--base BASE] [--zone ZONE] [--query QUERY] --to x,y,z
+                              [--rotation x,y,z,w] [--spread METERS] [--dir DIR] [--wait|--force]
+       msc_items_cleaner catalog
+       msc_items_cleaner baseline [<fresh items.txt>] [--dir DIR]
+       msc_items_cleaner generate <file> [--instances N] [--landfill PERCENT] [--consumed PERCENT] [--seed N]
+
+Cleans the items.txt in the current directory, or else the save game it finds on
+this computer. By default it removes everything in the landfill.
+\"explain\" shows what cleaning would do to the item with the given tag or ID,
+without changing anything. \"watch\" keeps running and cleans the save
+whenever the game was closed after saving. \"reinsert\" brings back items removed
+by earlier cleans, without items it lists what can be brought back. \"spawn\" adds
+new items of a kind (i.e. \"pikex\" or \"spraycan03\") at the given position,
+\"move\" puts the selected items there. \"catalog\" lists the items the cleaner
+knows about. \"baseline\" stores the items.txt of a fresh game next to the save,
+its items are never touched when cleaning, and lists the ones the save lost.
+
+Options:
+    --consumed              also remove items whose \"Consumed\" flag is set
+    --empty-spraycans       also remove spray cans without paint left, wherever they are
+    --shopping-bags         update the contents of shopping bags when their items are
+                            removed or renumbered (the format is unverified, see the README)
+    --condition-below X     also remove items with a condition below X
+    --zone NAME|x1,y1,z1,x2,y2,z2
+                            also remove items inside this zone (can be repeated),
+                            \"landfill\" is the area around the landfill spot
+    --protect PREFIX        never touch items whose ID starts with PREFIX (can be repeated)
+    --out-of-world delete|move|report
+                            find items that fell out of the world and remove them,
+                            move them to the rescue point or only report them
+    --world-bounds x1,y1,z1,x2,y2,z2
+                            where items have to be to not count as fallen out of
+                            the world (default -3000,-inf,-3000 to 3000,1000,3000)
+    --min-height Y          items below this height fell out of the world (default -100)
+    --rescue-point x,y,z    where \"--out-of-world move\" puts the items
+    --keep-at-most BASE=N   keep at most N items of the base (wildcards work like for
+                            --only), the consumed ones, the ones in the worst
+                            condition and the ones farthest from the house go first
+    --house x,y,z           where the house is, for --keep-at-most
+    --only BASES            only clean these item bases, comma separated, \"*\" and \"?\"
+                            work as wildcards (i.e. \"beercase,spraycan*\")
+    --exclude BASES         never clean these item bases, same format as --only
+    --category CATEGORIES   only clean the items of these categories, comma separated
+                            (food, drinks, car-fluids, car-parts, household, spraycans),
+                            together with --only
+    --bad-transforms fix|report
+                            find items with a corrupted rotation or scale and
+                            repair them or only report them
+    --explain               print why every item was kept or removed
+    --json                  print a JSON report instead of text (see the README for
+                            the exit codes)
+    --interactive           pick which of the items found by the rules get removed
+                            before saving
+    --dir DIR               clean the items.txt in DIR
+    --wait                  wait for My Summer Car to be closed if it's running
+    --force                 write even though My Summer Car is running
+    --help                  show this text";
+
+
+
+pub struct Options {
+    pub config: CleanConfig,   // which cleaning rules to run
+    pub explain: bool,         // print what happened to every item
+    pub interactive: bool,     // let the player pick what gets removed
+    pub if_running: IfRunning, // what to do if the game is running
+    pub dir: Option<PathBuf>,  // the directory containing the items.txt
+    pub json: bool             // print a JSON report for scripts
+}
+
+
+
+// Parses the arguments given to the program (without the program name)
+pub fn parse_options(args: &[String]) -> Options {
+    let mut options = Options {
+        config: CleanConfig::default(),
+        explain: false,
+        interactive: false,
+        if_running: IfRunning::Refuse,
+        dir: None,
+        json: false
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            },
+            "--explain" => options.explain = true,
+            "--json" => options.json = true,
+            "--interactive" => options.interactive = true,
+            "--wait" => options.if_running = IfRunning::Wait,
+            "--force" => options.if_running = IfRunning::Force,
+            "--dir" => options.dir = Some(flag_value(arg, args.next())),
+            _ if options.config.parse_flag(arg, &mut args) => (),
+            _ => exit(format!("Unknown argument \"{}\"\n\n{}", arg, USAGE).as_str())
+        }
+    }
+    if options.config.out_of_world == Some(WorldAction::Move) && options.config.rescue_point.is_none() {
+        exit("\"--out-of-world move\" needs a --rescue-point");
+    }
+    options
+}
@@ -0,0 +1,86 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Tells why items were kept, removed or renumbered, so it can be figured out
+// what happened to an item that vanished
+
+use std::collections::HashMap;
+
+use crate::rules::Verdict;
+use crate::{
+    CleanReport, ItemReport, baseline, clean_save, cli, display_tag, exit, filter, generate_entries,
+    get_item_id, read_items_file, rules, savedir
+};
+
+
+
+// Describes what happened to one item, i.e.
+//     pikex36: removed by "landfill" (Transform matches ...) at -679.33, 4.57, -727.30
+fn explain_item(item: &ItemReport, renamed: &HashMap<&[u8], &[u8]>, counters: &HashMap<&[u8], (u32, u32)>) -> String {
+    let mut line = format!("{}: ", display_tag(&item.id));
+    match &item.verdict {
+        Verdict::Keep => line.push_str("kept, no rule matched"),
+        Verdict::Delete(reason) => line.push_str(format!("removed by \"{}\" ({})", item.rule, reason).as_str()),
+        Verdict::Protect(reason) => line.push_str(format!("protected by \"{}\" ({})", item.rule, reason).as_str())
+    }
+    if let Some([x, y, z]) = item.position {
+        line.push_str(format!(", at {:.2}, {:.2}, {:.2}", x, y, z).as_str());
+    }
+    if let Some(newid) = renamed.get(item.id.as_slice()) {
+        line.push_str(format!(", renumbered to {}", display_tag(newid)).as_str());
+    }
+    if let Some((old, new)) = counters.get(item.id.as_slice()) {
+        line.push_str(format!(", counter {} -> {}", old, new).as_str());
+    }
+    line
+}
+
+
+
+// Explains every item of the report, or only the one with the given ID
+pub fn format_explanation(report: &CleanReport, only: Option<&[u8]>) -> String {
+    let renamed: HashMap<&[u8], &[u8]> = report.renamed.iter()
+        .map(|(old, new)| (old.as_slice(), new.as_slice()))
+        .collect();
+    let counters: HashMap<&[u8], (u32, u32)> = report.counters.iter()
+        .map(|(tag, old, new)| (tag.as_slice(), (*old, *new)))
+        .collect();
+
+    let mut out = String::new();
+    for item in report.items.iter().filter(|i| only.is_none_or(|id| i.id == id)) {
+        out.push_str(&explain_item(item, &renamed, &counters));
+        out.push('\n');
+    }
+    out
+}
+
+
+
+// The "explain" command: runs the cleaning on the items.txt without saving
+// anything and tells what it would do to the item with the given tag or ID
+pub fn run(args: &[String]) {
+    let Some(tag) = args.first().filter(|a| !a.starts_with("--")) else {
+        exit("Usage: explain <tag> [options], i.e. \"explain pikex36Transform\" or \"explain pikex36\"");
+    };
+    let mut options = cli::parse_options(&args[1..]);
+    if options.json {
+        exit("\"explain\" doesn't support --json");
+    }
+
+    let dir = savedir::choose(options.dir.as_ref());
+    options.config.defaults = baseline::load(&dir);
+    let items_file: Vec<u8> = read_items_file(&dir);
+    let (_, report) = clean_save(generate_entries(&items_file), &options.config, &rules::build_rules(&options.config));
+
+    let id = get_item_id(tag.as_bytes());
+    if !filter::is_included(&options.config, &id) {
+        println!("{}: left untouched, its base is filtered out by --only, --category or --exclude", display_tag(&id));
+        return;
+    }
+    let explanation = format_explanation(&report, Some(&id));
+    if explanation.is_empty() {
+        exit(format!("There's no item \"{}\" in \"items.txt\"", display_tag(&id)).as_str());
+    }
+    print!("{}", explanation);
+}
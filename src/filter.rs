@@ -0,0 +1,115 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Limits cleaning to some item bases with --only, --category and --exclude.
+// The entries of the other bases, their counters included, are set aside
+// before cleaning and put back unchanged afterwards.
+
+use std::collections::HashSet;
+
+use crate::rules::CleanConfig;
+use crate::{CATALOG, Entry, catalog_item, counter_tags, get_item_id, is_counter_tag, parse_tag};
+
+
+
+// Matches a text against a pattern where '*' stands for any number of
+// characters and '?' for exactly one, ignoring case
+pub fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| wildcard_match(rest, &text[i..])),
+        Some((b'?', rest)) => !text.is_empty() && wildcard_match(rest, &text[1..]),
+        Some((c, rest)) => text.first().is_some_and(|t| t.eq_ignore_ascii_case(c)) && wildcard_match(rest, &text[1..])
+    }
+}
+
+
+
+// The item base an entry belongs to. Counters don't start with the base
+// ("BeerCaseID" counts "beercase"), so they're looked up in the catalog.
+fn base_of(tag: &[u8]) -> &[u8] {
+    if is_counter_tag(tag) {
+        let counted = CATALOG.iter().find(|c| counter_tags(c).iter().any(|t| t.as_bytes() == tag));
+        if let Some(c) = counted {
+            return c.base.as_bytes();
+        }
+    }
+    // counters the catalog doesn't know, see 'counter_groups'
+    let base = parse_tag(tag).base;
+    if base == tag {
+        if let Some(counted) = tag.strip_suffix(b"ID") {
+            return counted;
+        }
+    }
+    base
+}
+
+
+
+// Checks whether the entries with this tag get cleaned: the base has to match
+// one of the --only patterns or be in one of the --category categories (if
+// there are any) and mustn't match any of the --exclude patterns
+pub fn is_included(config: &CleanConfig, tag: &[u8]) -> bool {
+    let base = base_of(tag);
+    let matches = |patterns: &[String]| patterns.iter().any(|p| wildcard_match(p.as_bytes(), base));
+    let in_category = catalog_item(base).is_some_and(|c| config.categories.contains(&c.category));
+    let wanted = (config.only.is_empty() && config.categories.is_empty()) || matches(&config.only) || in_category;
+    wanted && !matches(&config.exclude)
+}
+
+
+
+// The entries that aren't cleaned and where everything was in the save
+pub struct SetAside {
+    entries: Vec<(usize, Entry)>,   // the filtered out entries with their index in the save
+    included: Vec<(usize, Vec<u8>)> // index and item ID of the entries that get cleaned
+}
+
+
+
+// Takes the filtered out entries out of a save, returns the rest
+pub fn set_aside(entries: Vec<Entry>, config: &CleanConfig) -> (Vec<Entry>, SetAside) {
+    let mut res: Vec<Entry> = Vec::new();
+    let mut aside = SetAside { entries: Vec::new(), included: Vec::new() };
+    for (i, e) in entries.into_iter().enumerate() {
+        if is_included(config, &e.tag) {
+            aside.included.push((i, get_item_id(&e.tag)));
+            res.push(e);
+        } else {
+            aside.entries.push((i, e));
+        }
+    }
+    (res, aside)
+}
+
+
+
+// Puts the filtered out entries back where they were. 'cleaned' are the
+// entries left after cleaning, in their original order, and 'removed' the IDs
+// of the items cleaning removed.
+pub fn put_back(cleaned: Vec<Entry>, aside: SetAside, removed: &[Vec<u8>]) -> Vec<Entry> {
+    if aside.entries.is_empty() {
+        return cleaned;
+    }
+    let removed: HashSet<&[u8]> = removed.iter().map(Vec::as_slice).collect();
+    let mut cleaned = aside.included.iter()
+        .filter(|(_, id)| !removed.contains(id.as_slice()))
+        .map(|(i, _)| *i)
+        .zip(cleaned)
+        .peekable();
+    let mut set_aside = aside.entries.into_iter().peekable();
+
+    let mut res: Vec<Entry> = Vec::new();
+    loop {
+        let next_cleaned = cleaned.peek().map(|(i, _)| *i);
+        let next_aside = set_aside.peek().map(|(i, _)| *i);
+        match (next_cleaned, next_aside) {
+            (Some(c), Some(a)) if c < a => res.extend(cleaned.next().map(|(_, e)| e)),
+            (_, Some(_)) => res.extend(set_aside.next().map(|(_, e)| e)),
+            (Some(_), None) => res.extend(cleaned.next().map(|(_, e)| e)),
+            (None, None) => break
+        }
+    }
+    res
+}
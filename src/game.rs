@@ -0,0 +1,91 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Finds out whether My Summer Car is running. The game writes items.txt when
+// it saves, so anything written while it runs gets overwritten (or worse, the
+// game keeps using IDs that don't exist any more).
+
+use crate::{exit, info};
+
+
+
+const GAME_EXE: &str = "mysummercar.exe";
+
+
+
+// What to do if the game is running when items.txt is about to be written
+#[derive(Clone, Copy, PartialEq)]
+pub enum IfRunning {
+    Refuse, // quit with an error
+    Wait,   // wait until the game was closed
+    Force   // write anyway
+}
+
+
+
+// Whether a process command line belongs to the game. Under Proton/Wine the
+// arguments are separated by zeros and the executable is a Windows path, i.e.
+// "Z:\...\My Summer Car\mysummercar.exe".
+#[cfg(target_os = "linux")]
+pub fn is_game_cmdline(cmdline: &[u8]) -> bool {
+    cmdline.split(|&b| b == 0).any(|arg| {
+        let name = arg.rsplit(|&b| b == b'/' || b == b'\\').next().unwrap_or(arg);
+        name.eq_ignore_ascii_case(GAME_EXE.as_bytes())
+    })
+}
+
+
+
+// Looks through a directory laid out like /proc for the game, returns its PID
+#[cfg(target_os = "linux")]
+pub fn find_game_process(proc_dir: &std::path::Path) -> Option<u32> {
+    std::fs::read_dir(proc_dir).ok()?.flatten().find_map(|e| {
+        let pid: u32 = e.file_name().to_str()?.parse().ok()?;
+        let cmdline = std::fs::read(e.path().join("cmdline")).ok()?;
+        is_game_cmdline(&cmdline).then_some(pid)
+    })
+}
+
+
+
+#[cfg(target_os = "linux")]
+fn is_game_running() -> bool {
+    find_game_process(std::path::Path::new("/proc")).is_some()
+}
+
+#[cfg(target_os = "windows")]
+fn is_game_running() -> bool {
+    // tasklist prints "INFO: No tasks are running ..." if nothing matches the filter
+    let filter = format!("IMAGENAME eq {}", GAME_EXE);
+    match std::process::Command::new("tasklist").args(["/FI", filter.as_str(), "/NH"]).output() {
+        Ok(out) => String::from_utf8_lossy(&out.stdout).to_ascii_lowercase().contains(GAME_EXE),
+        Err(_) => false
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn is_game_running() -> bool {
+    false
+}
+
+
+
+// Makes sure the game isn't running before items.txt is touched, either by
+// quitting or by waiting for it to be closed
+pub fn ensure_not_running(if_running: IfRunning) {
+    if if_running == IfRunning::Force || !is_game_running() {
+        return;
+    }
+    if if_running == IfRunning::Refuse {
+        exit(
+            "My Summer Car is running and would overwrite \"items.txt\" the next time it saves! Close the game \
+             first, or pass --wait to wait for it to be closed (or --force to write anyway)."
+        );
+    }
+    info("My Summer Car is running, waiting for it to be closed...");
+    while is_game_running() {
+        std::thread::sleep(std::time::Duration::from_secs(2));
+    }
+}
+
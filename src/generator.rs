@@ -0,0 +1,186 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Builds synthetic but valid items.txt files, so tests, benchmarks and bug
+// reports don't need anybody's real save game
+
+use std::io::Write;
+
+use crate::{
+    CATALOG, DEFAULT_ITEMS, ES2_INT, Entry, LANDFILL_POS, counter_tags, exit, exit_on_error, flag_value,
+    write_entries
+};
+
+
+
+// What the generated save should contain
+pub struct GeneratorOptions {
+    pub instances: usize, // instances per item group (every spray can colour is a group of its own)
+    pub landfill: usize,  // percentage of instances that are put into the landfill
+    pub consumed: usize,  // percentage of instances that are consumed (empty for spray cans)
+    pub seed: u64         // the same seed always generates the same save
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> GeneratorOptions {
+        GeneratorOptions { instances: 10, landfill: 25, consumed: 25, seed: 1 }
+    }
+}
+
+
+
+// The 5 byte ES2 headers in front of the values. The int one is the one of
+// real saves (see 'ES2_INT'), the float and bool ones are placeholders as the
+// cleaner only ever looks at the value behind the header.
+const ES2_FLOAT: [u8; 5] = [0xFF, 0x6E, 0x1A, 0x0D, 0xAF];
+const ES2_BOOL: [u8; 5] = [0xFF, 0x3B, 0x54, 0xF1, 0x09];
+
+
+
+// Data of an int entry, i.e. a counter tag
+pub fn int_data(n: u32) -> Vec<u8> {
+    [&ES2_INT[..], &n.to_le_bytes()].concat()
+}
+
+
+
+// Data of a float entry, i.e. a "...Condition" tag
+pub fn float_data(f: f32) -> Vec<u8> {
+    [&ES2_FLOAT[..], &f.to_le_bytes()].concat()
+}
+
+
+
+// Data of a bool entry, i.e. a "...Consumed" tag
+pub fn bool_data(b: bool) -> Vec<u8> {
+    [&ES2_BOOL[..], &[b as u8]].concat()
+}
+
+
+
+// Data of a Transform entry at the given position, not rotated and with a
+// scale of 1.0 (see 'is_in_landfill' for the layout)
+pub fn transform_data(pos: [f32; 3]) -> Vec<u8> {
+    let mut data = vec![0xFF, 0x76, 0xFA, 0x7A, 0x09, 0x04];
+    for f in pos.iter().chain(&[0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]) {
+        data.extend_from_slice(&f.to_le_bytes());
+    }
+    data.extend_from_slice(b"\x08Untagged");
+    data
+}
+
+
+
+// Data of a Transform entry in the landfill, byte-exact so 'is_in_landfill' matches
+pub fn landfill_transform_data() -> Vec<u8> {
+    let mut data = transform_data([0.0; 3]);
+    data[6..18].copy_from_slice(&LANDFILL_POS);
+    data
+}
+
+
+
+// A small xorshift generator, good enough for scattering items around
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // A number in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // A number in 0.0..1.0
+    pub fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // A position somewhere on the map
+    pub fn position(&mut self) -> [f32; 3] {
+        [self.unit() * 4000.0 - 2000.0, self.unit() * 50.0, self.unit() * 4000.0 - 2000.0]
+    }
+}
+
+
+
+// Generates the entries of a save: the default items of a fresh game, then
+// every item group of the catalog with its instances and its counter tag.
+// Protected items aren't generated as the cleaner never touches them anyway.
+pub fn generate(options: &GeneratorOptions) -> Vec<Entry> {
+    let mut rng = Rng(options.seed.max(1)); // xorshift gets stuck on 0
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut push = |tag: String, data: Vec<u8>| entries.push(Entry { tag: tag.into_bytes(), data });
+
+    for item in DEFAULT_ITEMS {
+        let item = String::from_utf8_lossy(item);
+        push(format!("{}Transform", item), transform_data(rng.position()));
+        push(format!("{}Condition", item), float_data(1.0));
+    }
+
+    for c in CATALOG.iter().filter(|c| !c.protected) {
+        for (i, counter) in counter_tags(c).into_iter().enumerate() {
+            let colour = if c.colours == 0 { String::new() } else { format!("{:0>2}", i + 1) };
+            for id in 1..=options.instances {
+                let item = format!("{}{}{}", c.base, colour, id);
+                let transform = if rng.below(100) < options.landfill {
+                    landfill_transform_data()
+                } else {
+                    transform_data(rng.position())
+                };
+                let consumed = rng.below(100) < options.consumed;
+
+                push(format!("{}Transform", item), transform);
+                if c.colours == 0 {
+                    push(format!("{}Condition", item), float_data(rng.unit()));
+                    push(format!("{}Consumed", item), bool_data(consumed));
+                } else {
+                    let fill = if consumed { 0.0 } else { 0.05 + rng.unit() * 0.95 };
+                    push(format!("{}Fluid", item), float_data(fill));
+                }
+            }
+            push(counter, int_data(options.instances as u32));
+        }
+    }
+
+    entries
+}
+
+
+
+// The "generate" command: writes a synthetic save to the given file
+pub fn run(args: &[String]) {
+    let usage = "Usage: generate <file> [--instances N] [--landfill PERCENT] [--consumed PERCENT] [--seed N]";
+    let mut options = GeneratorOptions::default();
+    let mut path: Option<&String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--instances" => options.instances = flag_value(arg, args.next()),
+            "--landfill" => options.landfill = flag_value(arg, args.next()),
+            "--consumed" => options.consumed = flag_value(arg, args.next()),
+            "--seed" => options.seed = flag_value(arg, args.next()),
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => exit(format!("Unknown argument \"{}\"\n{}", arg, usage).as_str())
+        }
+    }
+
+    let path = path.unwrap_or_else(|| exit(usage));
+    if std::path::Path::new(path).exists() {
+        exit(format!("\"{}\" already exists, refusing to overwrite it", path).as_str());
+    }
+
+    let entries = generate(&options);
+    let file = exit_on_error(std::fs::File::create(path), format!("Failed to create \"{}\"", path).as_str());
+    let mut writer = std::io::BufWriter::new(file);
+    exit_on_error(write_entries(&mut writer, &entries), format!("I/O error while writing to \"{}\"", path).as_str());
+    exit_on_error(writer.flush(), format!("I/O error while writing to \"{}\"", path).as_str());
+    println!("Generated {} entries into \"{}\"", entries.len(), path);
+}
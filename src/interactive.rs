@@ -0,0 +1,242 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Lets the player go through the items the rules want to remove and pick
+// which of them really go, before anything is written
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use crate::rules::{CleanConfig, CleanRule, ItemGroup, Verdict, build_rules, parse_zone};
+use crate::{Entry, clean_save, display_name, display_tag, get_f32_value, get_item_id, parse_tag};
+
+
+
+const HELP: &str = "\
+Commands:
+    <n> or <n>-<m>  select or unselect the items with these numbers
+    b <base>        select or unselect all items of a kind, i.e. \"b pikex\"
+    z <zone>        select or unselect all items inside a zone, i.e. \"z landfill\"
+                    or \"z x1,y1,z1,x2,y2,z2\"
+    all, none       select or unselect everything
+    l [base]        list the items again, or only the ones of a kind
+    w               remove the selected items and save
+    q               quit without changing anything
+    h               show this text";
+
+
+
+// An item one of the rules wants to remove
+pub struct Candidate {
+    pub id: Vec<u8>,
+    pub rule: &'static str,
+    pub reason: String,
+    pub position: Option<[f32; 3]>,
+    pub condition: Option<f32>,
+    pub selected: bool           // whether it's going to be removed
+}
+
+
+
+// What to do after a command
+#[derive(PartialEq, Debug)]
+pub enum Action {
+    Continue,
+    Write,
+    Quit
+}
+
+
+
+// Wraps a rule so it doesn't delete the items the player unselected
+struct SpareRule {
+    rule: Box<dyn CleanRule>,
+    spared: Rc<HashSet<Vec<u8>>>
+}
+
+impl CleanRule for SpareRule {
+    fn name(&self) -> &'static str { self.rule.name() }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        match self.rule.check(group) {
+            Verdict::Delete(_) if self.spared.contains(group.id) => Verdict::Keep,
+            verdict => verdict
+        }
+    }
+
+    fn check_all(&self, kept: &[&ItemGroup]) -> Vec<(Vec<u8>, String)> {
+        self.rule.check_all(kept).into_iter().filter(|(id, _)| !self.spared.contains(id)).collect()
+    }
+}
+
+
+
+pub struct Selection {
+    pub candidates: Vec<Candidate>
+}
+
+impl Selection {
+    // Finds every item the configured rules would remove, all of them start
+    // out selected
+    pub fn new(entries: &[Entry], config: &CleanConfig) -> Selection {
+        let conditions: HashMap<Vec<u8>, f32> = entries.iter()
+            .filter(|e| parse_tag(&e.tag).property == b"Condition")
+            .filter_map(|e| Some((get_item_id(&e.tag), get_f32_value(e)?)))
+            .collect();
+        let (_, report) = clean_save(entries.to_vec(), config, &build_rules(config));
+
+        let candidates = report.items.into_iter()
+            .filter_map(|item| match item.verdict {
+                Verdict::Delete(reason) => Some(Candidate {
+                    condition: conditions.get(&item.id).copied(),
+                    id: item.id,
+                    rule: item.rule,
+                    reason,
+                    position: item.position,
+                    selected: true
+                }),
+                _ => None
+            })
+            .collect();
+        Selection { candidates }
+    }
+
+    // The rules that remove exactly the selected items
+    pub fn rules(&self, config: &CleanConfig) -> Vec<Box<dyn CleanRule>> {
+        let spared: Rc<HashSet<Vec<u8>>> = Rc::new(
+            self.candidates.iter().filter(|c| !c.selected).map(|c| c.id.clone()).collect()
+        );
+        build_rules(config).into_iter()
+            .map(|rule| Box::new(SpareRule { rule, spared: spared.clone() }) as Box<dyn CleanRule>)
+            .collect()
+    }
+
+    // Flips the given items: if any of them is selected all of them get
+    // unselected, otherwise all of them get selected
+    fn toggle(&mut self, indices: &[usize]) -> Result<(), String> {
+        if indices.is_empty() {
+            return Err(String::from("No items match"));
+        }
+        let select = !indices.iter().any(|&i| self.candidates[i].selected);
+        for &i in indices {
+            self.candidates[i].selected = select;
+        }
+        Ok(())
+    }
+
+    // Lists the candidates, only the ones of the given base if there is one
+    pub fn format_list(&self, base: Option<&str>) -> String {
+        let mut out = String::new();
+        for (i, c) in self.candidates.iter().enumerate() {
+            let parts = parse_tag(&c.id);
+            if base.is_some_and(|b| parts.base != b.as_bytes()) {
+                continue;
+            }
+            let position = match c.position {
+                Some([x, y, z]) => format!("{:.1}, {:.1}, {:.1}", x, y, z),
+                None => String::from("-")
+            };
+            let condition = c.condition.map_or(String::from("-"), |c| format!("{:.2}", c));
+            out.push_str(format!(
+                "{:>5} [{}] {:<20} {:<16} at {:<26} condition {:<5} {} ({})\n",
+                i + 1, if c.selected { 'x' } else { ' ' }, display_tag(&c.id), display_name(&c.id),
+                position, condition, c.rule, c.reason
+            ).as_str());
+        }
+        out
+    }
+
+    // What would happen with the current selection: how many items get
+    // removed and which counters change
+    pub fn format_summary(&self, entries: &[Entry], config: &CleanConfig) -> String {
+        let (_, report) = clean_save(entries.to_vec(), config, &self.rules(config));
+        let mut out = format!("{} of {} items selected for removal\n", report.removed.len(), self.candidates.len());
+        let changed: Vec<String> = report.counters.iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(tag, old, new)| format!("{} {} -> {}", display_tag(tag), old, new))
+            .collect();
+        if changed.is_empty() {
+            out.push_str("No counters change\n");
+        } else {
+            out.push_str(format!("Counters: {}\n", changed.join(", ")).as_str());
+        }
+        out
+    }
+
+    // Handles one line typed by the player
+    pub fn handle(&mut self, line: &str) -> Result<Action, String> {
+        let (cmd, arg) = match line.trim().split_once(' ') {
+            Some((cmd, arg)) => (cmd, arg.trim()),
+            None => (line.trim(), "")
+        };
+        match cmd {
+            "w" => return Ok(Action::Write),
+            "q" => return Ok(Action::Quit),
+            "h" | "?" => println!("{}", HELP),
+            "l" => print!("{}", self.format_list(Some(arg).filter(|a| !a.is_empty()))),
+            "all" | "none" => self.candidates.iter_mut().for_each(|c| c.selected = cmd == "all"),
+            "b" => {
+                let indices: Vec<usize> = (0..self.candidates.len())
+                    .filter(|&i| parse_tag(&self.candidates[i].id).base == arg.as_bytes())
+                    .collect();
+                self.toggle(&indices)?;
+            },
+            "z" => {
+                let zone = parse_zone(arg).ok_or(format!("Invalid zone \"{}\"", arg))?;
+                let indices: Vec<usize> = (0..self.candidates.len())
+                    .filter(|&i| self.candidates[i].position.is_some_and(|p| zone.contains(p)))
+                    .collect();
+                self.toggle(&indices)?;
+            },
+            _ => {
+                let (from, to) = cmd.split_once('-').unwrap_or((cmd, cmd));
+                let (Ok(from), Ok(to)) = (from.parse::<usize>(), to.parse::<usize>()) else {
+                    return Err(format!("Unknown command \"{}\", type \"h\" for help", line.trim()));
+                };
+                if from == 0 || to < from || to > self.candidates.len() {
+                    return Err(format!("There are only items 1 to {}", self.candidates.len()));
+                }
+                self.toggle(&(from - 1..to).collect::<Vec<usize>>())?;
+            }
+        }
+        Ok(Action::Continue)
+    }
+}
+
+
+
+// Asks the player which items to remove. Returns the rules removing exactly
+// these, or None if the player quit.
+pub fn select(entries: &[Entry], config: &CleanConfig) -> Option<Vec<Box<dyn CleanRule>>> {
+    let mut selection = Selection::new(entries, config);
+    if selection.candidates.is_empty() {
+        println!("There's nothing to remove");
+        return None;
+    }
+    print!("{}\n{}\n{}", selection.format_list(None), HELP, selection.format_summary(entries, config));
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush().ok()?;
+        let line = lines.next()?.ok()?;
+        match selection.handle(&line) {
+            Ok(Action::Continue) => print!("{}", selection.format_summary(entries, config)),
+            Ok(Action::Write) => break,
+            Ok(Action::Quit) => return None,
+            Err(msg) => println!("{}", msg)
+        }
+    }
+
+    print!("Remove the selected items and save \"items.txt\"? [y/N] ");
+    std::io::stdout().flush().ok()?;
+    let answer = lines.next()?.ok()?;
+    if answer.trim().eq_ignore_ascii_case("y") {
+        Some(selection.rules(config))
+    } else {
+        None
+    }
+}
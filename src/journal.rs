@@ -0,0 +1,140 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Keeps what every clean removed, so single items can be brought back later
+// without going back to a backup (and losing everything played since).
+//
+// The journal "items_journal.txt" uses the same format as the items.txt, so
+// the removed entries are stored exactly as they were. Every clean starts with
+// a "#clean" entry followed by the removed entries and the entries describing
+// the renumbering:
+//     #clean     the time of the clean, seconds since 1970 as u64
+//     <entries>  the removed entries, unchanged
+//     #renamed   old ID, a zero byte and the new ID of a renumbered item
+//     #counter   the counter tag, a zero byte and its old value as u32
+// Items can't start with '#', so these can't be confused with removed entries.
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::{CleanReport, Entry, Failure, get_item_id, io_failure, parse_entries, try_save_entries};
+
+
+
+const JOURNAL_FILE: &str = "items_journal.txt";
+
+// How many cleans are remembered
+const MAX_SESSIONS: usize = 10;
+
+
+
+// What one clean removed and changed
+pub struct Session {
+    pub time: u64,                         // seconds since 1970
+    pub removed: Vec<Entry>,               // the removed entries, unchanged
+    pub renamed: Vec<(Vec<u8>, Vec<u8>)>,  // old and new ID of every renumbered item
+    pub counters: Vec<(Vec<u8>, u32)>      // every counter tag with its value before the clean
+}
+
+impl Session {
+    // The IDs of the removed items, in the order they were in
+    pub fn removed_ids(&self) -> Vec<Vec<u8>> {
+        let mut res: Vec<Vec<u8>> = Vec::new();
+        for e in &self.removed {
+            let id = get_item_id(&e.tag);
+            if !res.contains(&id) {
+                res.push(id);
+            }
+        }
+        res
+    }
+}
+
+
+
+// Splits "a\0b" into "a" and "b"
+fn split_zero(data: &[u8]) -> (&[u8], &[u8]) {
+    match data.iter().position(|&b| b == 0) {
+        Some(i) => (&data[..i], &data[i + 1..]),
+        None => (data, &[])
+    }
+}
+
+
+
+// Turns the entries of a journal file into the cleans they describe
+pub fn from_entries(entries: Vec<Entry>) -> Vec<Session> {
+    let mut res: Vec<Session> = Vec::new();
+    for e in entries {
+        if e.tag == b"#clean" {
+            let time = e.data.get(..8).map_or(0, |t| u64::from_le_bytes(t.try_into().unwrap()));
+            res.push(Session { time, removed: Vec::new(), renamed: Vec::new(), counters: Vec::new() });
+            continue;
+        }
+        let Some(session) = res.last_mut() else { continue }; // nothing belongs to no clean
+        if e.tag == b"#renamed" {
+            let (old, new) = split_zero(&e.data);
+            session.renamed.push((old.to_vec(), new.to_vec()));
+        } else if e.tag == b"#counter" {
+            let (tag, value) = split_zero(&e.data);
+            let value = value.get(..4).map_or(0, |v| u32::from_le_bytes(v.try_into().unwrap()));
+            session.counters.push((tag.to_vec(), value));
+        } else {
+            session.removed.push(e);
+        }
+    }
+    res
+}
+
+
+
+// Turns cleans into the entries of a journal file
+pub fn to_entries(sessions: &[Session]) -> Vec<Entry> {
+    let mut res: Vec<Entry> = Vec::new();
+    for s in sessions {
+        res.push(Entry { tag: b"#clean".to_vec(), data: s.time.to_le_bytes().to_vec() });
+        res.extend(s.removed.iter().cloned());
+        for (old, new) in &s.renamed {
+            res.push(Entry { tag: b"#renamed".to_vec(), data: [&old[..], &[0], &new[..]].concat() });
+        }
+        for (tag, value) in &s.counters {
+            res.push(Entry { tag: b"#counter".to_vec(), data: [&tag[..], &[0], &value.to_le_bytes()[..]].concat() });
+        }
+    }
+    res
+}
+
+
+
+// Reads the journal in the given directory, oldest clean first
+pub fn read(dir: &Path) -> Result<Vec<Session>, Failure> {
+    let path = dir.join(JOURNAL_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let contents = io_failure(std::fs::read(&path), format!("Failed to read \"{}\"", path.display()).as_str())?;
+    Ok(from_entries(parse_entries(&contents)?))
+}
+
+
+
+// Replaces the journal in the given directory, only the newest cleans are kept
+pub fn write(dir: &Path, sessions: &[Session]) -> Result<(), Failure> {
+    let keep = &sessions[sessions.len().saturating_sub(MAX_SESSIONS)..];
+    try_save_entries(&dir.join(JOURNAL_FILE), &to_entries(keep))
+}
+
+
+
+// Adds a clean to the journal in the given directory
+pub fn record(dir: &Path, report: &CleanReport) -> Result<(), Failure> {
+    let mut sessions = read(dir)?;
+    sessions.push(Session {
+        time: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        removed: report.removed_entries.clone(),
+        renamed: report.renamed.clone(),
+        counters: report.counters.iter().map(|(tag, old, _)| (tag.clone(), *old)).collect()
+    });
+    write(dir, &sessions)
+}
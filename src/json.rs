@@ -0,0 +1,95 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// The --json output for launchers, mod managers and scripts. It's small
+// enough to be written by hand. Tags are written the same way they're
+// displayed, see 'display_tag'.
+
+use std::path::Path;
+
+use crate::rules::Verdict;
+use crate::{CleanReport, ExitCode, display_tag};
+
+
+
+// Turns a string into a JSON string literal, quotes included
+pub fn string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+
+
+// Puts the already formatted values into a JSON array
+fn array(values: Vec<String>) -> String {
+    if values.is_empty() {
+        return String::from("[]");
+    }
+    format!("[\n    {}\n  ]", values.join(",\n    "))
+}
+
+
+
+// The outcome of a run that got to the point of cleaning
+pub struct Outcome<'a> {
+    pub code: ExitCode,
+    pub entries_before: usize,
+    pub entries_after: usize,
+    pub backup: Option<&'a Path>, // None if items.txt wasn't written
+    pub report: &'a CleanReport
+}
+
+
+
+pub fn format_outcome(outcome: &Outcome) -> String {
+    let report = outcome.report;
+    let removed: Vec<String> = report.items.iter()
+        .filter_map(|item| match &item.verdict {
+            Verdict::Delete(reason) => Some(format!(
+                "{{\"id\": {}, \"rule\": {}, \"reason\": {}}}",
+                string(&display_tag(&item.id)), string(item.rule), string(reason)
+            )),
+            _ => None
+        })
+        .collect();
+    let moved: Vec<String> = report.moved.iter().map(|id| string(&display_tag(id))).collect();
+    let repaired: Vec<String> = report.repaired.iter().map(|id| string(&display_tag(id))).collect();
+    let renamed: Vec<String> = report.renamed.iter()
+        .map(|(old, new)| format!("{{\"from\": {}, \"to\": {}}}", string(&display_tag(old)), string(&display_tag(new))))
+        .collect();
+    let counters: Vec<String> = report.counters.iter()
+        .map(|(tag, old, new)| format!("{{\"tag\": {}, \"from\": {}, \"to\": {}}}", string(&display_tag(tag)), old, new))
+        .collect();
+    let warnings: Vec<String> = report.warnings.iter().map(|w| string(w)).collect();
+    let backup = outcome.backup.map_or(String::from("null"), |b| string(&b.display().to_string()));
+
+    format!(
+        "{{\n  \"status\": {},\n  \"exit_code\": {},\n  \"entries_before\": {},\n  \"entries_after\": {},\n  \
+         \"backup\": {},\n  \"removed\": {},\n  \"moved\": {},\n  \"repaired\": {},\n  \"renamed\": {},\n  \"counters\": {},\n  \"warnings\": {}\n}}",
+        string(outcome.code.name()), outcome.code as i32, outcome.entries_before, outcome.entries_after,
+        backup, array(removed), array(moved), array(repaired), array(renamed), array(counters), array(warnings)
+    )
+}
+
+
+
+// The output when the program has to quit early
+pub fn format_error(code: ExitCode, msg: &str) -> String {
+    format!(
+        "{{\n  \"status\": {},\n  \"exit_code\": {},\n  \"error\": {}\n}}",
+        string(code.name()), code as i32, string(msg)
+    )
+}
@@ -2,30 +2,151 @@
 * See LICENSE file for licensing information *
 *********************************************/
 
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod bags;
+mod baseline;
+#[cfg(feature = "bench")]
+mod bench;
+mod cli;
+mod explain;
+mod filter;
+mod game;
+mod generator;
+mod interactive;
+mod journal;
+mod json;
+mod reinsert;
+mod relocate;
+mod rules;
+mod savedir;
+mod select;
+mod spawn;
+#[cfg(test)]
+mod tests;
+mod transform;
+mod watch;
+mod world;
+
+use rules::{CleanRule, ItemGroup, Verdict};
 
 
 
 // An entry from the items.txt
+#[derive(Clone)]
 struct Entry {
-    tag: String, // the tag name
+    tag: Vec<u8>, // the tag name, exactly as it's stored in the file
     data: Vec<u8> // the binary data saved for this tag
 }
 
 
 
-// Receives a Result and an error message and calls 'exit' in case of an error
+// Turns a tag into something printable. Tags are kept as raw bytes so that
+// they're written back exactly as they were read, this is only for display.
+fn display_tag(tag: &[u8]) -> String {
+    tag.iter().map(|&b| b as char).collect()
+}
+
+
+
+// How the program ended, so scripts can tell without parsing the output. See
+// the README before changing any of the numbers.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ExitCode {
+    Cleaned = 0,          // items.txt was cleaned and saved
+    NothingToDo = 1,      // there was nothing to clean, items.txt wasn't touched
+    Error = 2,            // invalid arguments, the game is running, ...
+    ParseError = 3,       // items.txt is damaged or not an items.txt at all
+    IoError = 4,          // reading or writing a file failed
+    ValidationFailed = 5  // the cleaned save can't be saved or didn't read back correctly
+}
+
+impl ExitCode {
+    // The name used in the JSON output
+    fn name(self) -> &'static str {
+        match self {
+            ExitCode::Cleaned => "cleaned",
+            ExitCode::NothingToDo => "nothing_to_do",
+            ExitCode::Error => "error",
+            ExitCode::ParseError => "parse_error",
+            ExitCode::IoError => "io_error",
+            ExitCode::ValidationFailed => "validation_failed"
+        }
+    }
+}
+
+
+
+// Set by --json: stdout only gets the JSON report, everything meant for humans
+// goes to stderr
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+
+
+// Prints a message meant for humans
+fn info(msg: &str) {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        eprintln!("{}", msg);
+    } else {
+        println!("{}", msg);
+    }
+}
+
+
+
+// Receives a Result and an error message and quits with an I/O error in case of an error
 fn exit_on_error<T>(r: std::io::Result<T>, error_msg: &str) -> T {
-    if r.is_err() {
-        exit(error_msg)
+    match r {
+        Ok(v) => v,
+        Err(_) => exit_with(ExitCode::IoError, error_msg)
+    }
+}
+
+
+
+// An error that ends the program, kept as a value for the places that can
+// carry on after one (see 'watch')
+struct Failure {
+    code: ExitCode,
+    msg: String
+}
+
+
+
+// Turns an I/O error into a Failure with the given message
+fn io_failure<T>(r: std::io::Result<T>, error_msg: &str) -> Result<T, Failure> {
+    r.map_err(|_| Failure { code: ExitCode::IoError, msg: error_msg.to_string() })
+}
+
+
+
+// Quits with the failure's exit code and message in case of one
+fn exit_on_failure<T>(r: Result<T, Failure>) -> T {
+    match r {
+        Ok(v) => v,
+        Err(f) => exit_with(f.code, f.msg.as_str())
     }
-    return r.ok().unwrap();
 }
 
 
 
 // Prints an error message and quits the program
-fn exit(msg: &str) {
+fn exit(msg: &str) -> ! {
+    exit_with(ExitCode::Error, msg)
+}
+
+
+
+// Prints an error message and quits the program with the given exit code
+fn exit_with(code: ExitCode, msg: &str) -> ! {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        println!("{}", json::format_error(code, msg));
+        std::process::exit(code as i32);
+    }
+
     println!("{}", msg);
     
     #[cfg(target_os = "windows")]
@@ -35,18 +156,24 @@ fn exit(msg: &str) {
         std::io::stdin().read_line(&mut line).unwrap();
     }
 
-    std::process::exit(-1);
+    std::process::exit(code as i32);
+}
+
+
+
+// Parses the value following a command line flag, quits if it's missing or invalid
+fn flag_value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> T {
+    match value.map(|v| v.parse()) {
+        Some(Ok(v)) => v,
+        _ => exit(format!("Missing or invalid value for \"{}\"", flag).as_str())
+    }
 }
 
 
 
 // Reads a little-endian u32 from the provided array
-fn get_u32_le(buf: &Vec<u8>, idx: &mut usize) -> u32 {
-    let res: u32 =
-    ((buf[*idx + 0] as u32) <<  0) |
-    ((buf[*idx + 1] as u32) <<  8) |
-    ((buf[*idx + 2] as u32) << 16) |
-    ((buf[*idx + 3] as u32) << 24) ;
+fn get_u32_le(buf: &[u8], idx: &mut usize) -> u32 {
+    let res = u32::from_le_bytes([buf[*idx], buf[*idx + 1], buf[*idx + 2], buf[*idx + 3]]);
     *idx += 4;
     res
 }
@@ -55,96 +182,250 @@ fn get_u32_le(buf: &Vec<u8>, idx: &mut usize) -> u32 {
 
 // Creates a little-endian byte-vector from the provided number
 fn mk_u32_le(n: &usize) -> Vec<u8> {
-    vec![
-        (n >>  0 & 0xFF) as u8,
-        (n >>  8 & 0xFF) as u8,
-        (n >> 16 & 0xFF) as u8,
-        (n >> 24 & 0xFF) as u8
-    ]
+    (*n as u32).to_le_bytes().to_vec()
 }
 
 
 
-// Reads a string with a given length from the provided array
-// the given index will be incremented by the amount of bytes read for
-// convenience
-fn get_string(buf: &Vec<u8>, idx: &mut usize, len: u32) -> String {
-    let mut res = String::new();
-    let mut i = 0;
-    while i < len {
-        res.push(buf[*idx + (i as usize)] as char);
-        i += 1;
+// An entry borrowed from the contents of an items.txt, so parsing doesn't
+// need to copy anything
+#[derive(Clone, Copy)]
+struct EntryRef<'a> {
+    tag: &'a [u8],
+    data: &'a [u8]
+}
+
+impl EntryRef<'_> {
+    // Copies the borrowed entry into one that can be modified
+    fn to_entry(self) -> Entry {
+        Entry { tag: self.tag.to_vec(), data: self.data.to_vec() }
     }
-    *idx += len as usize;
-    res
 }
 
 
 
-// Expects the data from items.txt and generates the entries from it
-fn generate_entries(file_contents: Vec<u8>) -> Vec<Entry> {
-    let mut result = Vec::new();
-    let mut i: usize = 0;
-    
-    while i < file_contents.len() {
-        let mut new_entry: Entry = Entry { tag: String::new(), data: Vec::new() };
+// Walks over the contents of an items.txt and yields one entry after another.
+// It stops at the first damaged entry, 'error' tells why.
+struct EntryParser<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    error: Option<String>
+}
+
+impl<'a> EntryParser<'a> {
+    fn new(buf: &'a [u8]) -> EntryParser<'a> {
+        EntryParser { buf, pos: 0, error: None }
+    }
+
+    // Stops parsing with the given error
+    fn fail<T>(&mut self, msg: String) -> Option<T> {
+        self.error = Some(msg);
+        self.pos = self.buf.len();
+        None
+    }
+
+    // Returns the next 'len' bytes and moves on, fails if the file ends early
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.buf.len() - self.pos < len {
+            return self.fail(format!("Unexpected end of file at position {:#10x}", self.pos));
+        }
+        let res = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Some(res)
+    }
+}
+
+impl<'a> Iterator for EntryParser<'a> {
+    type Item = EntryRef<'a>;
+
+    fn next(&mut self) -> Option<EntryRef<'a>> {
+        if self.pos >= self.buf.len() {
+            return None;
+        }
 
         // check entry header
-        if file_contents[i] != 0x7E {
-            exit(format!("Invalid header symbol at position {:#10x}", i).as_str());
+        let start = self.pos;
+        if self.take(1)?[0] != 0x7E {
+            return self.fail(format!("Invalid header symbol at position {:#10x}", start));
         }
-        i += 1;
 
         // read tag name
-        let tag_length = file_contents[i];
-        i += 1;
-        new_entry.tag = get_string(&file_contents, &mut i, tag_length as u32);
+        let tag_length = self.take(1)?[0] as usize;
+        let tag = self.take(tag_length)?;
 
         // read data
-        let data_length = get_u32_le(&file_contents, &mut i);
-        for j in 0..data_length-1 { // -1 because the final byte is the footer
-            new_entry.data.push(file_contents[i + j as usize]);
+        let data_length_pos = self.pos;
+        let mut idx = 0;
+        let data_length = get_u32_le(self.take(4)?, &mut idx) as usize;
+        if data_length == 0 {
+            return self.fail(format!("Invalid data length at position {:#10x}", data_length_pos));
         }
-        i += (data_length - 1) as usize;
+        let data = self.take(data_length - 1)?; // -1 because the final byte is the footer
 
         // check entry footer
-        if file_contents[i] != 0x7B {
-            exit(format!("Invalid footer symbol at position {:#10x}", i).as_str());
+        let footer_pos = self.pos;
+        if self.take(1)?[0] != 0x7B {
+            return self.fail(format!("Invalid footer symbol at position {:#10x}", footer_pos));
         }
-        i += 1;
 
-        result.push(new_entry);
+        Some(EntryRef { tag, data })
+    }
+}
+
+
+
+// Expects the data from items.txt and generates the entries from it
+fn parse_entries(file_contents: &[u8]) -> Result<Vec<Entry>, Failure> {
+    let mut parser = EntryParser::new(file_contents);
+    let entries: Vec<Entry> = parser.by_ref().map(EntryRef::to_entry).collect();
+    match parser.error {
+        Some(msg) => Err(Failure { code: ExitCode::ParseError, msg }),
+        None => Ok(entries)
     }
-    result
 }
 
 
 
-// Saves the entries into items.txt, overwriting it (make sure to call 'backup_items_file' first)
-fn save_new_items_file(entries: &Vec<Entry>) {
-    let items_file_path = std::path::PathBuf::from("items.txt");
-    exit_on_error(std::fs::remove_file(&items_file_path), "Failed to delete \"items.txt\"");
-    let items_file = exit_on_error(std::fs::File::create(&items_file_path), "Failed to create \"items.txt\"");
-    let mut writer = std::io::BufWriter::new(items_file);
+// Like 'parse_entries', but quits if the data is damaged
+fn generate_entries(file_contents: &[u8]) -> Vec<Entry> {
+    exit_on_failure(parse_entries(file_contents))
+}
+
+
+
+// Checks that every entry fits into its length prefixes, the tag length is a
+// u8 and the data length a u32
+fn check_lengths(entries: &[Entry]) -> std::io::Result<()> {
+    for e in entries {
+        if e.tag.len() > u8::MAX as usize {
+            let msg = format!("The tag \"{}\" is longer than 255 bytes and can't be saved", display_tag(&e.tag));
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+        if e.data.len() >= u32::MAX as usize { // >= because the length includes the footer
+            let msg = format!("The data of \"{}\" is too large to be saved", display_tag(&e.tag));
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+    }
+    Ok(())
+}
+
+
 
+// Writes the entries in the items.txt format. Every entry is put together in
+// one buffer first (reused for all of them) so it can be written with a single
+// call. Nothing is written if one of them doesn't fit, see 'check_lengths'.
+fn write_entries<W: Write>(writer: &mut W, entries: &[Entry]) -> std::io::Result<()> {
+    check_lengths(entries)?;
+    let mut buf: Vec<u8> = Vec::new();
     for e in entries {
+        buf.clear();
         // header
-        exit_on_error(writer.write(&[0x7E as u8]), "I/O error while writing to \"items.txt\"");
+        buf.push(0x7E);
         // tag name
-        exit_on_error(writer.write(&[e.tag.len() as u8]), "I/O error while writing to \"items.txt\"");
-        exit_on_error(writer.write(&e.tag.as_bytes()), "I/O error while writing to \"items.txt\"");
+        buf.push(e.tag.len() as u8);
+        buf.extend_from_slice(&e.tag);
         // data
-        exit_on_error(writer.write(&mk_u32_le(&(&e.data.len() + 1))), "I/O error while writing to \"items.txt\""); // + 1 because the length includes the footer
-        exit_on_error(writer.write(&e.data), "I/O error while writing to \"items.txt\"");
+        buf.extend_from_slice(&((e.data.len() + 1) as u32).to_le_bytes()); // + 1 because the length includes the footer
+        buf.extend_from_slice(&e.data);
         // footer
-        exit_on_error(writer.write(&[0x7B as u8]), "I/O error while writing to \"items.txt\"");
+        buf.push(0x7B);
+        writer.write_all(&buf)?;
     }
+    Ok(())
+}
+
 
-    exit_on_error(writer.flush(), "I/O error while writing to \"items.txt\"");
+
+// Reads the items.txt in the given directory
+fn try_read_items_file(dir: &std::path::Path) -> Result<Vec<u8>, Failure> {
+    let path = dir.join("items.txt");
+    io_failure(
+        std::fs::read(&path),
+        format!("File \"{}\" was not found or couldn't be read!", path.display()).as_str()
+    )
 }
 
 
 
+// Like 'try_read_items_file', but quits if it fails
+fn read_items_file(dir: &std::path::Path) -> Vec<u8> {
+    exit_on_failure(try_read_items_file(dir))
+}
+
+
+
+// Writes the entries into the given file, replacing it if it exists
+fn try_save_entries(file_path: &std::path::Path, entries: &[Entry]) -> Result<(), Failure> {
+    // 'write_entries' checks this too, but by then the old file is gone
+    check_lengths(entries).map_err(|e| Failure { code: ExitCode::ValidationFailed, msg: e.to_string() })?;
+
+    let path = file_path.display();
+    if file_path.is_file() {
+        io_failure(std::fs::remove_file(file_path), format!("Failed to delete \"{}\"", path).as_str())?;
+    }
+    let file = io_failure(std::fs::File::create(file_path), format!("Failed to create \"{}\"", path).as_str())?;
+    let mut writer = std::io::BufWriter::new(file);
+
+    io_failure(write_entries(&mut writer, entries), format!("I/O error while writing to \"{}\"", path).as_str())?;
+    io_failure(writer.flush(), format!("I/O error while writing to \"{}\"", path).as_str())
+}
+
+
+
+// Like 'try_save_entries', but quits if it fails
+fn save_entries(file_path: &std::path::Path, entries: &[Entry]) {
+    exit_on_failure(try_save_entries(file_path, entries));
+}
+
+
+
+// Reads the position from the data of a Transform entry, see 'is_in_landfill'
+// for the layout
+fn decode_position(data: &[u8]) -> Option<[f32; 3]> {
+    let bytes = data.get(6..18)?;
+    let f = |i: usize| f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    Some([f(0), f(4), f(8)])
+}
+
+
+
+// Writes the position into the data of a Transform entry, returns false if
+// the data is too short to hold one
+fn set_position(data: &mut [u8], pos: [f32; 3]) -> bool {
+    let Some(bytes) = data.get_mut(6..18) else { return false };
+    for (i, f) in pos.iter().enumerate() {
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&f.to_le_bytes());
+    }
+    true
+}
+
+
+
+// Writes the rotation (a quaternion, x, y, z, w) into the data of a Transform
+// entry, returns false if the data is too short to hold one
+fn set_rotation(data: &mut [u8], rotation: [f32; 4]) -> bool {
+    let Some(bytes) = data.get_mut(18..34) else { return false };
+    for (i, f) in rotation.iter().enumerate() {
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&f.to_le_bytes());
+    }
+    true
+}
+
+
+
+// Parses a position given on the command line, "x,y,z"
+fn parse_position(s: &str) -> Option<[f32; 3]> {
+    let n: Vec<f32> = s.split(',').map(|n| n.trim().parse().ok()).collect::<Option<_>>()?;
+    n.try_into().ok()
+}
+
+
+
+// The raw bytes of the dedicated landfill position, see 'is_in_landfill'
+const LANDFILL_POS: [u8; 12] = [0xFA, 0xD4, 0x29, 0xC4, 0xB8, 0x4F, 0x92, 0x40, 0xEF, 0xD2, 0x35, 0xC4];
+
+
+
 // Checks whether an item is located at the dedicated landfill position
 fn is_in_landfill(entry: &Entry) -> bool {
     // Example:
@@ -153,44 +434,348 @@ fn is_in_landfill(entry: &Entry) -> bool {
     //                         X:                    |---------|
     //                         Y:                                |---------|
     //                         Z:                                            |---------|
-    let landfill_pos: [u8; 12] = [0xFA, 0xD4, 0x29, 0xC4, 0xB8, 0x4F, 0x92, 0x40, 0xEF, 0xD2, 0x35, 0xC4];
-
-    if !entry.tag.ends_with("Transform") || entry.data.len() < 18 {
+    if !entry.tag.ends_with(b"Transform") || entry.data.len() < 18 {
         return false
     }
 
     let mut same = true;
     for i in 6..18 { // Thanks Rust, really intuitive to write "6..18" when I want to run up to 17
-        same &= entry.data[i] == landfill_pos[i-6];
+        same &= entry.data[i] == LANDFILL_POS[i-6];
     }
     same
 }
 
 
-// Returns the base item name (i.e. "pikex12Transform" -> "pikex" or "spraycan0122" -> "spraycan01")
-fn get_item_base(tag: &String) -> String {
-    let tag_array = tag.as_bytes();
-    for i in 0..tag_array.len() {
-        let c = tag_array[i];
-        if c >= '0' as u8 && c <= '9' as u8 {
-            let to_add = if tag.starts_with("spraycan") { 2 } else { 0 }; // +2 because spraycans have a numeric ID
-            return String::from_utf8(tag_array[0..(i+to_add)].to_vec()).unwrap()
+// What kind of item something is, for --category and for humans
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Category {
+    Food,
+    Drinks,
+    CarFluids,
+    CarParts,
+    Household,
+    Spraycans
+}
+
+impl Category {
+    const ALL: [Category; 6] = [
+        Category::Food, Category::Drinks, Category::CarFluids, Category::CarParts, Category::Household, Category::Spraycans
+    ];
+
+    // The name used on the command line
+    fn name(self) -> &'static str {
+        match self {
+            Category::Food => "food",
+            Category::Drinks => "drinks",
+            Category::CarFluids => "car-fluids",
+            Category::CarParts => "car-parts",
+            Category::Household => "household",
+            Category::Spraycans => "spraycans"
         }
     }
-    String::from(tag)
 }
 
-// Trims the item id from a full tag name, i.e. "pikex36Transform" -> "pikex36"
-fn get_item_id(tag: &String) -> String {
-    let tag_array = tag.as_bytes();
-    let base = get_item_base(&tag);
-    for i in base.len()..tag_array.len() {
-        let c = tag_array[i];
-        if c < '0' as u8 || c > '9' as u8 {
-            return String::from_utf8(tag_array[0..i].to_vec()).unwrap();
+impl std::str::FromStr for Category {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Category, ()> {
+        Category::ALL.into_iter().find(|c| c.name() == s).ok_or(())
+    }
+}
+
+
+
+// An item type the cleaner knows about
+struct CatalogItem {
+    base: &'static str,            // the tag prefix shared by all instances, i.e. "pikex"
+    name: &'static str,            // what the game calls it, i.e. "Pike"
+    category: Category,
+    consumable: bool,              // gets used up: eaten, drunk, smoked, poured, sprayed or burned
+    colours: usize,                // number of two-digit colour variants following the base (0 if there are none)
+    counter: Option<&'static str>, // the tag holding the highest instance ID, i.e. "pikexID" ("##" is replaced by the colour)
+    protected: bool                // can be attached to the car, the house, or the radio
+}
+
+
+
+// All item bases the tag parser knows about. Some of them are prefixes of each
+// other (i.e. "spark plug" and "spark plug box"), the parser always picks the
+// longest one that fits.
+//
+// The protected items can end up in the landfill but can be attached to the
+// car, the house, or the radio so (for now) we'll not touch these as they
+// surely are referenced by ID somewhere else and changing IDs might cause some
+// save file or game corruption if not handled properly.
+//
+// Checking the defaultES2file.txt suggests that most stuff that's mounted
+// into/on the car gets removed from items.txt and gets moved there, but
+// further checks are needed before handling them.
+const CATALOG: &[CatalogItem] = &[
+    CatalogItem { base: "beercase", name: "Beer case", category: Category::Drinks, consumable: true, colours: 0, counter: Some("BeerCaseID"), protected: false },
+    CatalogItem { base: "sausagesx", name: "Sausages", category: Category::Food, consumable: true, colours: 0, counter: Some("SausagesxID"), protected: false },
+    CatalogItem { base: "milkx", name: "Milk", category: Category::Drinks, consumable: true, colours: 0, counter: Some("milkxID"), protected: false },
+    CatalogItem { base: "sugar", name: "Sugar", category: Category::Food, consumable: true, colours: 0, counter: Some("sugarID"), protected: false },
+    CatalogItem { base: "yeast", name: "Yeast", category: Category::Food, consumable: true, colours: 0, counter: Some("yeastID"), protected: false },
+    CatalogItem { base: "potatochips", name: "Potato chips", category: Category::Food, consumable: true, colours: 0, counter: Some("potatochipsID"), protected: false },
+    CatalogItem { base: "pizzax", name: "Pizza", category: Category::Food, consumable: true, colours: 0, counter: Some("pizzaxID"), protected: false },
+    CatalogItem { base: "macaronboxx", name: "Macaroni box", category: Category::Food, consumable: true, colours: 0, counter: Some("macaronboxxID"), protected: false },
+    CatalogItem { base: "macaron boxx", name: "Macaroni box", category: Category::Food, consumable: true, colours: 0, counter: None, protected: false },
+    CatalogItem { base: "shoppingbagx", name: "Shopping bag", category: Category::Household, consumable: false, colours: 0, counter: Some("shoppingbagxID"), protected: false },
+    CatalogItem { base: "moosemeatx", name: "Moose meat", category: Category::Food, consumable: true, colours: 0, counter: Some("moosemeatxID"), protected: false },
+    CatalogItem { base: "Booze", name: "Booze", category: Category::Drinks, consumable: true, colours: 0, counter: Some("BoozeID"), protected: false },
+    CatalogItem { base: "pikex", name: "Pike", category: Category::Food, consumable: true, colours: 0, counter: Some("pikexID"), protected: false },
+    CatalogItem { base: "juiceconcentrate", name: "Juice concentrate", category: Category::Drinks, consumable: true, colours: 0, counter: Some("juiceconcentrateID"), protected: false },
+    CatalogItem { base: "motoroil", name: "Motor oil", category: Category::CarFluids, consumable: true, colours: 0, counter: Some("motoroilID"), protected: false },
+    CatalogItem { base: "brakefluid", name: "Brake fluid", category: Category::CarFluids, consumable: true, colours: 0, counter: Some("brakefluidID"), protected: false },
+    CatalogItem { base: "coolant", name: "Coolant", category: Category::CarFluids, consumable: true, colours: 0, counter: Some("coolantID"), protected: false },
+    CatalogItem { base: "twostroke", name: "Two stroke fuel oil", category: Category::CarFluids, consumable: true, colours: 0, counter: Some("twostrokeID"), protected: false },
+    CatalogItem { base: "cigarettes", name: "Cigarettes", category: Category::Household, consumable: true, colours: 0, counter: Some("cigarettesID"), protected: false },
+    CatalogItem { base: "spark plug box", name: "Spark plug box", category: Category::CarParts, consumable: false, colours: 0, counter: Some("sparkplugboxID"), protected: false },
+    CatalogItem { base: "groundcoffee", name: "Ground coffee", category: Category::Drinks, consumable: true, colours: 0, counter: Some("groundcoffeeID"), protected: false },
+    CatalogItem { base: "grillcharcoal", name: "Grill charcoal", category: Category::Household, consumable: true, colours: 0, counter: Some("grillcharcoalID"), protected: false },
+    CatalogItem { base: "light bulb box", name: "Light bulb box", category: Category::CarParts, consumable: false, colours: 0, counter: Some("lightbulbboxID"), protected: false },
+    CatalogItem { base: "fuse package", name: "Fuse package", category: Category::CarParts, consumable: false, colours: 0, counter: Some("fusepackageID"), protected: false },
+    CatalogItem { base: "r20 battery box", name: "R20 battery box", category: Category::Household, consumable: false, colours: 0, counter: Some("r20batteryboxID"), protected: false },
+    CatalogItem { base: "mosquitospray", name: "Mosquito spray", category: Category::Household, consumable: true, colours: 0, counter: Some("mosquitosprayID"), protected: false },
+    // Spraycans come in 13 colours, each one counted separately, i.e.
+    // "spraycan0322Transform" is can #22 of colour 03 and counted by "Spraycan03ID"
+    CatalogItem { base: "spraycan", name: "Spray can", category: Category::Spraycans, consumable: true, colours: 13, counter: Some("Spraycan##ID"), protected: false },
+    // Protected, see above
+    CatalogItem { base: "fireextinguisher", name: "Fire extinguisher", category: Category::CarParts, consumable: false, colours: 0, counter: Some("fireextinguisherID"), protected: true },
+    CatalogItem { base: "n2obottle", name: "N2O bottle", category: Category::CarParts, consumable: true, colours: 0, counter: None, protected: true }, // ID correct?
+    CatalogItem { base: "battery", name: "Car battery", category: Category::CarParts, consumable: false, colours: 0, counter: Some("batteryID"), protected: true },
+    CatalogItem { base: "oil filter", name: "Oil filter", category: Category::CarParts, consumable: false, colours: 0, counter: Some("oilfilterID"), protected: true },
+    CatalogItem { base: "spark plug", name: "Spark plug", category: Category::CarParts, consumable: false, colours: 0, counter: Some("sparkplugID"), protected: true },
+    CatalogItem { base: "alternator belt", name: "Alternator belt", category: Category::CarParts, consumable: false, colours: 0, counter: Some("alternatorbeltID"), protected: true },
+    CatalogItem { base: "light bulb", name: "Light bulb", category: Category::CarParts, consumable: false, colours: 0, counter: Some("lightbulbID"), protected: true },
+    CatalogItem { base: "fuse", name: "Fuse", category: Category::CarParts, consumable: false, colours: 0, counter: Some("fuseID"), protected: true },
+    CatalogItem { base: "fuseholder", name: "Fuse holder", category: Category::CarParts, consumable: false, colours: 0, counter: None, protected: true },
+    CatalogItem { base: "r20 battery", name: "R20 battery", category: Category::Household, consumable: false, colours: 0, counter: Some("r20batteryID"), protected: true },
+];
+
+
+
+// A tag split into its parts, i.e. "pikex36Transform" -> ("pikex", "", "36", "Transform")
+// or "spraycan0322Transform" -> ("spraycan", "03", "22", "Transform")
+struct TagParts<'a> {
+    base: &'a [u8],     // the item base, "pikex"
+    colour: &'a [u8],   // the colour of items that come in several ones, "03" (empty otherwise)
+    id: &'a [u8],       // the instance ID, "36" (empty for items without one, i.e. "milkxTransform")
+    property: &'a [u8]  // what the tag stores, "Transform"
+}
+
+
+
+// Returns the counter tags of a catalog item, coloured items have one per colour
+fn counter_tags(c: &CatalogItem) -> Vec<String> {
+    match c.counter {
+        None => Vec::new(),
+        Some(counter) if c.colours == 0 => vec![String::from(counter)],
+        Some(counter) => (1..=c.colours)
+            .map(|colour| counter.replace("##", &format!("{:0>2}", colour)))
+            .collect()
+    }
+}
+
+
+
+// The ES2 header in front of int values, as stored by the counter tags
+const ES2_INT: [u8; 5] = [0xFF, 0x56, 0x08, 0xA8, 0xE2];
+
+
+
+// Checks whether an entry looks like a counter, known or not: its name ends in
+// "ID" and it stores an int
+fn looks_like_counter(e: &Entry) -> bool {
+    e.tag.ends_with(b"ID") && e.data.len() == 9 && e.data.starts_with(&ES2_INT)
+}
+
+
+
+// A counter tag and the items it counts
+struct CounterGroup {
+    tag: Vec<u8>,
+    base: Vec<u8>,
+    colour: Vec<u8>
+}
+
+
+
+// The item groups that get renumbered: the ones of the catalog and the ones
+// found in the save. Counters the catalog doesn't know are paired with the
+// base of items that have a Transform if their names match ("fishtrapID"
+// counts "fishtrap1Transform", ...), these groups are warned about.
+fn counter_groups(entries: &[Entry], warnings: &mut Vec<String>) -> Vec<CounterGroup> {
+    let mut res: Vec<CounterGroup> = Vec::new();
+    for c in CATALOG.iter().filter(|c| !c.protected) {
+        for (i, tag) in counter_tags(c).into_iter().enumerate() {
+            let colour = if c.colours == 0 { Vec::new() } else { format!("{:0>2}", i + 1).into_bytes() };
+            res.push(CounterGroup { tag: tag.into_bytes(), base: c.base.as_bytes().to_vec(), colour });
         }
     }
-    String::from(tag)
+
+    let normalise = |name: &[u8]| -> Vec<u8> {
+        name.iter().filter(|&&c| c != b' ').map(u8::to_ascii_lowercase).collect()
+    };
+    let mut bases: Vec<&[u8]> = Vec::new();
+    for e in entries {
+        let parts = parse_tag(&e.tag);
+        if parts.property == b"Transform" && !parts.id.is_empty() && !bases.contains(&parts.base) {
+            bases.push(parts.base);
+        }
+    }
+    for e in entries.iter().filter(|e| !is_counter_tag(&e.tag) && looks_like_counter(e)) {
+        let name = normalise(&e.tag[..e.tag.len() - 2]);
+        let Some(&base) = bases.iter().find(|b| normalise(b) == name) else { continue };
+        if catalog_item(base).is_some_and(|c| c.protected) || res.iter().any(|g| g.tag == e.tag) {
+            continue;
+        }
+        warnings.push(format!(
+            "found the unknown item group \"{}\" counted by \"{}\", it's renumbered like the known ones",
+            display_tag(base), display_tag(&e.tag)
+        ));
+        res.push(CounterGroup { tag: e.tag.clone(), base: base.to_vec(), colour: Vec::new() });
+    }
+    res
+}
+
+
+
+// Checks whether a tag is one of the counters holding the highest ID of an item group
+fn is_counter_tag(tag: &[u8]) -> bool {
+    static COUNTER_TAGS: OnceLock<HashSet<Vec<u8>>> = OnceLock::new();
+    COUNTER_TAGS
+        .get_or_init(|| CATALOG.iter().flat_map(counter_tags).map(String::into_bytes).collect())
+        .contains(tag)
+}
+
+
+
+// Returns the length of the colour at the start of the rest of a tag after the
+// base, or None if the catalog item has colours but the tag doesn't name a
+// valid one
+fn colour_len(c: &CatalogItem, rest: &[u8]) -> Option<usize> {
+    if c.colours == 0 {
+        return Some(0);
+    }
+    let digits = rest.get(..2)?;
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let colour = ((digits[0] - b'0') * 10 + (digits[1] - b'0')) as usize;
+    if (1..=c.colours).contains(&colour) { Some(2) } else { None }
+}
+
+
+
+// Returns the amount of ASCII digits at the start of a byte string
+fn count_digits(bytes: &[u8]) -> usize {
+    bytes.iter().take_while(|b| b.is_ascii_digit()).count()
+}
+
+
+
+// Checks whether the rest of a tag after a base looks like "<digits><Property>"
+fn is_id_and_property(rest: &[u8]) -> bool {
+    match rest.get(count_digits(rest)) {
+        None => true,
+        Some(c) => c.is_ascii_uppercase()
+    }
+}
+
+
+
+// Splits a tag into base, colour, instance ID and property by picking the
+// longest known item base from the catalog that it starts with. Unknown tags
+// are cut at the first digit, or are taken as a whole if they don't contain one.
+fn parse_tag(tag: &[u8]) -> TagParts<'_> {
+    let (base_len, colour_len) = CATALOG.iter()
+        .filter_map(|c| {
+            let rest = tag.strip_prefix(c.base.as_bytes())?;
+            let colour_len = colour_len(c, rest)?;
+            if is_id_and_property(&rest[colour_len..]) { Some((c.base.len(), colour_len)) } else { None }
+        })
+        .max()
+        .unwrap_or_else(|| (tag.iter().position(u8::is_ascii_digit).unwrap_or(tag.len()), 0));
+
+    let colour_end = base_len + colour_len;
+    let id_end = colour_end + count_digits(&tag[colour_end..]);
+    TagParts {
+        base: &tag[..base_len],
+        colour: &tag[base_len..colour_end],
+        id: &tag[colour_end..id_end],
+        property: &tag[id_end..]
+    }
+}
+
+
+
+// Trims the item id from a full tag name, i.e. "pikex36Transform" -> "pikex36"
+// or "milkxTransform" -> "milkx". Counter tags aren't items, so they're
+// returned as they are.
+fn get_item_id(tag: &[u8]) -> Vec<u8> {
+    if is_counter_tag(tag) {
+        return tag.to_vec();
+    }
+    let parts = parse_tag(tag);
+    [parts.base, parts.colour, parts.id].concat()
+}
+
+
+
+// The "catalog" command's output: every item base the cleaner knows about
+fn format_catalog() -> String {
+    let mut out = format!(
+        "{:<18} {:<20} {:<10} {:<10} {:<26} {}\n",
+        "base", "name", "category", "consumable", "counter", "protected"
+    );
+    for c in CATALOG {
+        let counter = match c.counter {
+            Some(counter) if c.colours > 0 => format!("{} ({} colours)", counter, c.colours),
+            Some(counter) => String::from(counter),
+            None => String::from("-")
+        };
+        out.push_str(format!(
+            "{:<18} {:<20} {:<10} {:<10} {:<26} {}\n",
+            c.base, c.name, c.category.name(), if c.consumable { "yes" } else { "no" }, counter,
+            if c.protected { "yes" } else { "no" }
+        ).as_str());
+    }
+    out
+}
+
+
+
+// Looks up the catalog item of an item base
+fn catalog_item(base: &[u8]) -> Option<&'static CatalogItem> {
+    CATALOG.iter().find(|c| c.base.as_bytes() == base)
+}
+
+
+
+// The name of the item a tag belongs to, i.e. "Pike" for "pikex36Transform".
+// Items missing from the catalog are called by their base.
+fn display_name(tag: &[u8]) -> String {
+    let base = parse_tag(tag).base;
+    catalog_item(base).map_or_else(|| display_tag(base), |c| String::from(c.name))
+}
+
+
+
+// Checks whether the item base of a tag is marked as protected in the catalog
+fn is_protected(tag: &[u8]) -> bool {
+    catalog_item(parse_tag(tag).base).is_some_and(|c| c.protected)
+}
+
+
+
+// Returns the counter tag of the group an item belongs to, i.e. "pikexID" for
+// "pikex3" or "Spraycan03ID" for "spraycan0322"
+fn counter_tag_of(id: &[u8]) -> Option<String> {
+    let parts = parse_tag(id);
+    let c = CATALOG.iter().find(|c| !c.protected && c.base.as_bytes() == parts.base)?;
+    let i = if c.colours == 0 { 0 } else { parse_id(parts.colour)?.checked_sub(1)? };
+    counter_tags(c).into_iter().nth(i)
 }
 
 
@@ -198,312 +783,452 @@ fn get_item_id(tag: &String) -> String {
 // Sets the "count" of a tag to a new one
 // (i.e. "sausagesx11Transform" -> "sausagesx7Transform")
 fn tag_set_new_count(e: &mut Entry, n: usize) {
-    let tag_clone = e.tag.clone();
-    let tag_array = tag_clone.as_bytes();
+    let parts = parse_tag(&e.tag);
+    e.tag = [parts.base, parts.colour, n.to_string().as_bytes(), parts.property].concat();
+}
 
-    let start_num_pos;
-    let mut end_num_pos = 0;
 
-    let base = get_item_base(&tag_clone);
-    start_num_pos = base.len();
-    for i in start_num_pos..tag_array.len() {
-        let c = tag_array[i];
-        if c < '0' as u8 || c > '9' as u8 {
-            end_num_pos = i;
-            break;
-        }
+
+// The property holding how much paint is left in a spray can, i.e.
+// "spraycan0322Fluid". The name is an assumption, no fixture was taken from a
+// real save with spray cans yet.
+const SPRAYCAN_FILL_PROPERTY: &[u8] = b"Fluid";
+
+
+
+// Reads the float stored in an entry, which follows the 5 byte ES2 type header
+fn get_f32_value(entry: &Entry) -> Option<f32> {
+    let bytes = entry.data.get(5..9)?;
+    Some(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+
+
+// Parses the instance ID of a tag, i.e. "36" -> 36
+fn parse_id(id: &[u8]) -> Option<usize> {
+    std::str::from_utf8(id).ok()?.parse().ok()
+}
+
+
+
+// These items are present on a fresh save game and if touched weird things
+// happen, probably because TG hardcoded some stuff. So we won't touch them.
+
+// TODO: Verify whether this is still true after getting everything to work
+//       properly!
+const DEFAULT_ITEMS: [&[u8]; 6] = [
+    b"milkx",
+    b"sausagesx0",
+    b"pizzax",
+    b"beercase0",
+    b"macaron boxx",
+    b"oil filter0",
+];
+
+
+
+// What the rules decided for one item
+struct ItemReport {
+    id: Vec<u8>,                 // the item ID before renumbering
+    rule: &'static str,          // the rule that decided, empty if none did
+    verdict: Verdict,            // what happened to the item and why
+    position: Option<[f32; 3]>   // where the item was, if it has a Transform
+}
+
+
+
+// What 'clean_entries' did to a save
+#[derive(Default)]
+struct CleanReport {
+    items: Vec<ItemReport>,             // every item, in the order they appear in
+    removed: Vec<Vec<u8>>,              // the ids of the removed items
+    removed_entries: Vec<Entry>,        // the entries of the removed items, for the journal
+    moved: Vec<Vec<u8>>,                // the ids of the items moved back into the world
+    repaired: Vec<Vec<u8>>,             // the ids of the items whose Transform was repaired
+    renamed: Vec<(Vec<u8>, Vec<u8>)>,   // old and new id of every renumbered item
+    counters: Vec<(Vec<u8>, u32, u32)>, // every counter tag with its old and new value
+    warnings: Vec<String>               // things that look wrong but didn't stop the cleaning
+}
+
+
+
+// Turns a report into one line per change
+#[cfg(any(debug_assertions, test))]
+fn format_report(report: &CleanReport) -> String {
+    let mut out = String::new();
+    for id in &report.removed {
+        out.push_str(format!("removed {}\n", display_tag(id)).as_str());
+    }
+    for id in &report.moved {
+        out.push_str(format!("moved {}\n", display_tag(id)).as_str());
     }
+    for id in &report.repaired {
+        out.push_str(format!("repaired {}\n", display_tag(id)).as_str());
+    }
+    for (oldid, newid) in &report.renamed {
+        out.push_str(format!("renamed {} -> {}\n", display_tag(oldid), display_tag(newid)).as_str());
+    }
+    for (tag, old, new) in &report.counters {
+        out.push_str(format!("counter {} {} -> {}\n", display_tag(tag), old, new).as_str());
+    }
+    for warning in &report.warnings {
+        out.push_str(format!("warning {}\n", warning).as_str());
+    }
+    out
+}
 
-    e.tag = format!("{}{}{}",
-        base,
-        n,
-        String::from_utf8(tag_array[end_num_pos..tag_array.len()].to_vec()).unwrap()
-    );
+
+
+// The one line summary printed after cleaning
+fn format_summary(report: &CleanReport) -> String {
+    let moved = if report.moved.is_empty() {
+        String::new()
+    } else {
+        format!(", moved {} items back into the world", report.moved.len())
+    };
+    let repaired = if report.repaired.is_empty() {
+        String::new()
+    } else {
+        format!(", repaired {} transforms", report.repaired.len())
+    };
+    format!(
+        "Removed {} items{}{}, renumbered {} items and updated {} counters",
+        report.removed.len(),
+        moved,
+        repaired,
+        report.renamed.len(),
+        report.counters.iter().filter(|(_, old, new)| old != new).count()
+    )
 }
 
 
 
-// Removes unwanted entries from the provided ones
-fn clean_entries(entries: Vec<Entry>) -> Vec<Entry> {
-    
-    // In this initial version of the program we'll simply delete all items that
-    // are in the dedicated landfill spot. This is probably the safest thing to
-    // do, even if it won't fully "clear" the save of all used up items.
-
-    // These are present on a fresh save game and if touched weird things happen,
-    // probably because TG hardcoded some stuff. So we won't touch those entries.
-
-    // TODO: Verify whether this is still true after getting everything to work
-    //       properly!
-    let dont_touch_entries = vec![
-        "milkxTransform",
-        "milkxCondition",
-        "sausagesx0",
-        "pizzaxTransform",
-        "pizzaxCondition",
-        "beercase0",
-        "macaron boxxTransform",
-        "macaron boxxCondition",
-        "oilfilter0",
-    ];
+// Whether cleaning changed anything at all
+fn changed_anything(report: &CleanReport) -> bool {
+    !report.removed.is_empty()
+        || !report.moved.is_empty()
+        || !report.repaired.is_empty()
+        || !report.renamed.is_empty()
+        || report.counters.iter().any(|(_, old, new)| old != new)
+}
 
-    // A blacklist of tag-beginnings that can end up in the landfill but can be
-    // attached to the car, the house, or the radio so (for now) we'll not touch
-    // these as they surely are referenced by ID somewhere else and changing IDs
-    // might cause some save file or game corruption if not handled properly
-    let blacklist = vec![
-        "fireextinguisher",
-        "n2obottle", // ID correct?
-        "battery",
-        "oil filter,",
-        "spark plug",
-        "alternator belt",
-        "light bulb",
-        "fuse",
-        "r20 battery"
-    ];
 
-    // determine the items that are in the landfill
-    let mut located_in_landfill: Vec<String> = Vec::new();
-    for e in &entries {
-        let itemid = get_item_id(&e.tag);
 
-        if
-            is_in_landfill(e) &&
-            !dont_touch_entries.contains(&itemid.as_str()) &&
-            blacklist.iter().all(|bi| !e.tag.starts_with(bi))
-        {
-            located_in_landfill.push(itemid);
+// Runs all rules on every item: the first protecting rule wins, otherwise the
+// first deleting one does. Returns the name of the deciding rule per item.
+fn judge_all(groups: &[ItemGroup], rules: &[Box<dyn CleanRule>]) -> Vec<(&'static str, Verdict)> {
+    let held: Vec<HashMap<Vec<u8>, String>> = rules.iter()
+        .map(|rule| rule.protect_all(groups).into_iter().collect())
+        .collect();
+    groups.iter().map(|group| {
+        let mut res = ("", Verdict::Keep);
+        for (rule, held) in rules.iter().zip(&held) {
+            let verdict = match held.get(group.id) {
+                Some(reason) => Verdict::Protect(reason.clone()),
+                None => rule.check(group)
+            };
+            match verdict {
+                Verdict::Protect(reason) => return (rule.name(), Verdict::Protect(reason)),
+                Verdict::Delete(reason) if matches!(res.1, Verdict::Keep) => res = (rule.name(), Verdict::Delete(reason)),
+                _ => ()
+            }
+        }
+        res
+    }).collect()
+}
+
+
+
+// The IDs of the items one of the rules protects. Moving and repairing items
+// leaves them alone just like removing does.
+fn protected_ids(entries: &[Entry], rules: &[Box<dyn CleanRule>]) -> HashSet<Vec<u8>> {
+    let ids: Vec<Vec<u8>> = entries.iter().map(|e| get_item_id(&e.tag)).collect();
+    let groups = group_entries(entries, &ids);
+    groups.iter().zip(judge_all(&groups, rules))
+        .filter(|(_, (_, verdict))| matches!(verdict, Verdict::Protect(_)))
+        .map(|(g, _)| g.id.to_vec())
+        .collect()
+}
+
+
+
+// Cleans a save: the item bases left out by --only and --exclude are set
+// aside (see 'filter'), the items that fell out of the world are moved or
+// reported and corrupted transforms are repaired or reported if it's
+// configured (see 'world' and 'transform'), then the rules are run. With
+// --shopping-bags the contents of the bags that were cleaned are updated
+// afterwards (see 'bags').
+fn clean_save(entries: Vec<Entry>, config: &rules::CleanConfig, rules: &[Box<dyn CleanRule>]) -> (Vec<Entry>, CleanReport) {
+    let (mut entries, aside) = filter::set_aside(entries, config);
+    let protected = protected_ids(&entries, rules);
+    let (moved, world_warnings) = world::rescue(&mut entries, config, &protected);
+    let (repaired, transform_warnings) = transform::check_transforms(&mut entries, config, &protected);
+    let (mut entries, mut report) = clean_entries(entries, rules);
+    report.moved = moved;
+    report.repaired = repaired;
+    report.warnings.extend(world_warnings);
+    report.warnings.extend(transform_warnings);
+    if config.shopping_bags {
+        let bag_warnings = bags::update_contents(&mut entries, &report.removed, &report.renamed);
+        report.warnings.extend(bag_warnings);
+    }
+    let entries = filter::put_back(entries, aside, &report.removed);
+    (entries, report)
+}
+
+
+
+// Groups the entries by item, in the order the items first appear in. 'ids'
+// holds the item ID of every entry.
+fn group_entries<'a>(entries: &'a [Entry], ids: &'a [Vec<u8>]) -> Vec<ItemGroup<'a>> {
+    let mut groups: Vec<ItemGroup> = Vec::new();
+    let mut group_index: HashMap<&[u8], usize> = HashMap::new();
+    for (e, id) in entries.iter().zip(ids) {
+        let gi = *group_index.entry(id).or_insert_with(|| {
+            groups.push(ItemGroup { id, entries: Vec::new() });
+            groups.len() - 1
+        });
+        groups[gi].entries.push(e);
+    }
+    groups
+}
+
+
+
+// Removes unwanted items from the provided entries. Every item (all entries
+// sharing an item ID) is checked by the rules, see 'rules::build_rules'.
+// Afterwards the remaining items are renumbered.
+fn clean_entries(entries: Vec<Entry>, rules: &[Box<dyn CleanRule>]) -> (Vec<Entry>, CleanReport) {
+    let mut report = CleanReport::default();
+
+    let ids: Vec<Vec<u8>> = entries.iter().map(|e| get_item_id(&e.tag)).collect();
+    let groups = group_entries(&entries, &ids);
+
+    let mut removed: HashSet<Vec<u8>> = HashSet::new();
+    let mut protected: HashSet<Vec<u8>> = HashSet::new();
+    for (group, (rule, verdict)) in groups.iter().zip(judge_all(&groups, rules)) {
+        match verdict {
+            Verdict::Delete(_) => {
+                removed.insert(group.id.to_vec());
+                report.removed.push(group.id.to_vec());
+            },
+            Verdict::Protect(_) => {
+                protected.insert(group.id.to_vec());
+            },
+            Verdict::Keep => ()
+        }
+        report.items.push(ItemReport { id: group.id.to_vec(), rule, verdict, position: group.position() });
+    }
+
+    // the rules comparing the items that are left with each other
+    let kept: Vec<&ItemGroup> = groups.iter()
+        .zip(&report.items)
+        .filter(|(_, item)| matches!(item.verdict, Verdict::Keep))
+        .map(|(group, _)| group)
+        .collect();
+    let item_index: HashMap<Vec<u8>, usize> = report.items.iter().enumerate().map(|(i, item)| (item.id.clone(), i)).collect();
+    for rule in rules {
+        for (id, reason) in rule.check_all(&kept) {
+            let Some(&i) = item_index.get(&id) else { continue };
+            let item = &mut report.items[i];
+            if removed.insert(id.clone()) {
+                item.rule = rule.name();
+                item.verdict = Verdict::Delete(reason);
+                report.removed.push(id);
+            }
         }
     }
 
-    // push everything that's not in the landfill into the result vector
     let mut res: Vec<Entry> = Vec::new();
-    for e in entries {
-        let itemid = get_item_id(&e.tag);
-        if !located_in_landfill.contains(&itemid) {
-            res.push(e)
+    for (e, id) in entries.into_iter().zip(ids) {
+        if removed.contains(&id) {
+            report.removed_entries.push(e);
+        } else {
+            res.push(e);
         }
     }
+    let reserved: Vec<Vec<u8>> = rules.iter().flat_map(|rule| rule.reserved()).collect();
+    renumber_entries(&mut res, &protected, &reserved, &mut report);
+    (res, report)
+}
 
-    /*
-     * In future versions we also want to remove all items that are not in the
-     * landfill but are still consumed. For some items this can be checked with
-     * the "...Consumed" entry, but it's more complicated for others
-     * (i.e. pikes only have a "Condition" variable)
-     */
-    
-    
-    // recount and set all entry IDs so that they start at 1, except for the special cases
+
+
+// Recounts and sets all item IDs so that they start at 1 and sets the counter
+// tags to the highest ID of their group. Protected items (see 'CATALOG')
+// aren't counted, the items in 'keep' hold on to their ID and no other item
+// will get it, just like the IDs in 'reserved' whether their items are in
+// the save or not. Items with colours get one group per colour.
+fn renumber_entries(entries: &mut [Entry], keep: &HashSet<Vec<u8>>, reserved: &[Vec<u8>], report: &mut CleanReport) {
     struct Group {
-        tagname: String,
-        tagid: String,
-        has_default_zero_item: bool,
-        count: usize,
+        tagid: Vec<u8>,
+        reserved: HashSet<usize>, // the IDs of the kept items
+        items: Vec<Vec<u8>>,      // the IDs of the items to renumber, in the order they appear in
         max: usize
     }
-    let mut item_counts: Vec<Group> = vec![
-        Group { tagname: String::from("beercase"), tagid: String::from("BeerCaseID"), has_default_zero_item: true, count: 0, max: 0 },
-        Group { tagname: String::from("sausagesx"), tagid: String::from("SausagesxID"), has_default_zero_item: true, count: 0, max: 0 },
-        Group { tagname: String::from("milkx"), tagid: String::from("milkxID"), has_default_zero_item: true, count: 0, max: 0 },
-        Group { tagname: String::from("sugar"), tagid: String::from("sugarID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("yeast"), tagid: String::from("yeastID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("potatochips"), tagid: String::from("potatochipsID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("pizzax"), tagid: String::from("pizzaxID"), has_default_zero_item: true, count: 0, max: 0 },
-        Group { tagname: String::from("macaronbox"), tagid: String::from("macaronboxxID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("shoppingbagx"), tagid: String::from("shoppingbagxID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("moosemeatx"), tagid: String::from("moosemeatxID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("Booze"), tagid: String::from("BoozeID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("pikex"), tagid: String::from("pikexID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("juiceconcentrate"), tagid: String::from("juiceconcentrateID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("motoroil"), tagid: String::from("motoroilID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("brakefluid"), tagid: String::from("brakefluidID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("coolant"), tagid: String::from("coolantID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("twostroke"), tagid: String::from("twostrokeID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("cigarettes"), tagid: String::from("cigarettesID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spark plug box"), tagid: String::from("sparkplugboxID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("groundcoffee"), tagid: String::from("groundcoffeeID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("grillcharcoal"), tagid: String::from("grillcharcoalID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("light bulb box"), tagid: String::from("lightbulbboxID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("fuse package"), tagid: String::from("fusepackageID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("r20 battery box"), tagid: String::from("r20batteryboxID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("mosquitospray"), tagid: String::from("mosquitosprayID"), has_default_zero_item: false, count: 0, max: 0 },
-        // Spraycans are untested, here be dragons        
-        Group { tagname: String::from("spraycan01"), tagid: String::from("Spraycan01ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan02"), tagid: String::from("Spraycan02ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan03"), tagid: String::from("Spraycan03ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan04"), tagid: String::from("Spraycan04ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan05"), tagid: String::from("Spraycan05ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan06"), tagid: String::from("Spraycan06ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan07"), tagid: String::from("Spraycan07ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan08"), tagid: String::from("Spraycan08ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan09"), tagid: String::from("Spraycan09ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan10"), tagid: String::from("Spraycan10ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan11"), tagid: String::from("Spraycan11ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan12"), tagid: String::from("Spraycan12ID"), has_default_zero_item: false, count: 0, max: 0 },
-        Group { tagname: String::from("spraycan13"), tagid: String::from("Spraycan13ID"), has_default_zero_item: false, count: 0, max: 0 },
-    ];
-
-    // These items here won't yet be touched, see the comment on the variable
-    // 'blacklist' for an explanation
-
-    // fireextinguisher, fireextinguisherID
-    // battery, batteryID
-    // oil filter, oilfilterID
-    // spark plug, sparkplugID
-    // alternator belt, alternatorbeltID
-    // light bulb, lightbulbID
-    // fuse, fuseID -> most likely fuseholderXX entries in items.txt
-    // r20 battery, r20batteryID
-
-    // Checking the defaultES2file.txt suggests that most stuff that's mounted
-    // into/on the car gets removed from items.txt and gets moved there, but
-    // further checks are needed before implementing it.
-    
-    
-    // Count items, modify entry counters
-    for e in &mut res {
-        for g in &mut item_counts {
-            // We check for Transform here because a) it always exists, and
-            // b) because otherwise we'd be counting things like
-            // "yeast12Transform" and "yeast12Consumed" twice
-            if e.tag.starts_with(&g.tagname) && e.tag.ends_with("Transform") {
-                g.count += 1;
-                g.max += 1;
-            }
+    let counted = counter_groups(entries, &mut report.warnings);
+    let mut item_counts: Vec<Group> = Vec::new();
+    // (base, colour) -> index into 'item_counts'
+    let mut group_index: HashMap<(Vec<u8>, Vec<u8>), usize> = HashMap::new();
+    for g in counted {
+        group_index.insert((g.base, g.colour), item_counts.len());
+        item_counts.push(Group { tagid: g.tag, reserved: HashSet::new(), items: Vec::new(), max: 0 });
+    }
+    for id in reserved {
+        let parts = parse_tag(id);
+        if let Some(&gi) = group_index.get(&(parts.base.to_vec(), parts.colour.to_vec())) {
+            item_counts[gi].reserved.extend(parse_id(parts.id));
         }
     }
 
-    // Recude counters by 1 (where possible) because the field holds the highest
-    // item group ID, not the count
-    for g in &mut item_counts {
-        if g.count >= 1 {
-            if g.has_default_zero_item {
-                g.count -= 1;
-                g.max -= 1
-            }
+    // collect the items of every group
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    for e in entries.iter() {
+        if is_counter_tag(&e.tag) {
+            continue;
+        }
+        let parts = parse_tag(&e.tag);
+        let Some(&gi) = group_index.get(&(parts.base.to_vec(), parts.colour.to_vec())) else { continue };
+        let id = get_item_id(&e.tag);
+        if keep.contains(&id) {
+            item_counts[gi].reserved.extend(parse_id(parts.id));
+        } else if !parts.id.is_empty() && seen.insert(id.clone()) {
+            item_counts[gi].items.push(id);
         }
     }
 
-    #[derive(Clone)]
-    struct Map {
-        oldid: String,
-        newid: String
+    // hand out the lowest free IDs, the first item gets the highest one
+
+    // TODO: In the original file the IDs are descending. Is this a requirement?
+
+    // old item id -> new counter
+    let mut map: HashMap<Vec<u8>, usize> = HashMap::new();
+    for g in &mut item_counts {
+        let free: Vec<usize> = (1..).filter(|n| !g.reserved.contains(n)).take(g.items.len()).collect();
+        g.max = free.last().copied().unwrap_or(0).max(g.reserved.iter().copied().max().unwrap_or(0));
+        for (id, n) in g.items.iter().zip(free.into_iter().rev()) {
+            map.insert(id.clone(), n);
+        }
     }
-    let mut map: Vec<Map> = Vec::new();
 
     // rename items
-    for e in &mut res {
-        if
-            dont_touch_entries.contains(&e.tag.as_str()) ||
-            blacklist.iter().any(|bi| e.tag.starts_with(bi))
-        {
+    let mut reported: HashSet<Vec<u8>> = HashSet::new();
+    for e in entries.iter_mut() {
+        if is_counter_tag(&e.tag) {
             continue;
         }
-
-        for g in &mut item_counts {
-            if g.tagid == e.tag {
-                continue;
-            }
-
-            if e.tag.starts_with(&g.tagname) {
-                // Look up tag in map
-                let id = get_item_id(&e.tag);
-                match map.iter().find(|&e| e.oldid == id) {
-                    Some(m) => {
-                        // if found then we just replace the tag with the mapped one
-                        let mapped_item = m.clone();
-                        e.tag = e.tag.replace(&mapped_item.oldid, &mapped_item.newid)
-                    },
-                    None => {
-                        // otherwise we add it to the map with the new counter
-                        tag_set_new_count(e, g.count);
-                        if g.count > 0 {
-                            g.count -= 1;
-                        }
-                        map.push(Map { oldid: id, newid: get_item_id(&e.tag) });
-                    }
-                };
+        let id = get_item_id(&e.tag);
+        if let Some(&newcount) = map.get(&id) {
+            tag_set_new_count(e, newcount);
+            let newid = get_item_id(&e.tag);
+            if newid != id && reported.insert(id.clone()) {
+                report.renamed.push((id, newid));
             }
         }
     }
 
     // finally: find BeerCaseID, SausagesxID, milkxID, sugarID, yeastID,
-    //          potatochipsID, pizzaxID, macaronboxID, shoppingbagxID,
-    //          moosemeatxID, BoozeID, pikexID (and maybe some others in the
-    //          future) and set their IDs to the highest ID of the corresponding
-    //          item group
-
-    // TODO: In the original file the IDs are descending. Is this a requirement?
-
-    for e in &mut res {
-        for g in &item_counts {
-            if e.tag == g.tagid {
-                let count = mk_u32_le(&g.max);
-                /*
-                 * BeerCaseID:  FF 56 08 A8 E2 (0A 00 00 00)
-                 * SausagesxID: FF 56 08 A8 E2 (36 00 00 00)
-                 * ...
-                 */
-                for pos in 5..9 {
-                    e.data[pos] = count[pos - 5];
-                }
+    //          potatochipsID, pizzaxID, macaronboxxID, shoppingbagxID,
+    //          moosemeatxID, BoozeID, pikexID, Spraycan01ID (and the ones
+    //          found in the save, see 'counter_groups') and set their IDs to
+    //          the highest ID of the corresponding item group
+    let counter_index: HashMap<&[u8], usize> = item_counts.iter()
+        .enumerate()
+        .map(|(gi, g)| (g.tagid.as_slice(), gi))
+        .collect();
+    for e in entries.iter_mut() {
+        if let Some(&gi) = counter_index.get(e.tag.as_slice()) {
+            if e.data.len() < 9 {
+                report.warnings.push(format!("the counter tag \"{}\" is too short to be updated", display_tag(&e.tag)));
+                continue;
             }
+            let g = &item_counts[gi];
+            let mut idx = 5;
+            report.counters.push((g.tagid.clone(), get_u32_le(&e.data, &mut idx), g.max as u32));
+            let count = mk_u32_le(&g.max);
+            /*
+             * BeerCaseID:  FF 56 08 A8 E2 (0A 00 00 00)
+             * SausagesxID: FF 56 08 A8 E2 (36 00 00 00)
+             * ...
+             */
+            e.data[5..9].copy_from_slice(&count);
         }
     }
-
-    res
 }
 
 
 
-// Creates a safety-save of the items.txt
-fn backup_items_file() {
+// Creates a safety-save of the items.txt in the given directory, returns its path
+fn backup_items_file(dir: &std::path::Path) -> Result<std::path::PathBuf, Failure> {
     // creates a filepath with the given number in it
-    fn fnamep(i: usize) -> std::path::PathBuf {
-        std::path::PathBuf::from(format!("items{:0>2}.txt", i))
-    }
+    let fnamep = |i: usize| dir.join(format!("items{:0>2}.txt", i));
 
     // how many backups should be held
     let max_backup_counter: usize = 10;
     {
         let p = fnamep(max_backup_counter);
         if std::path::Path::is_file(&p) {
-            exit_on_error(std::fs::remove_file(&p), format!("Failed to remove file \"{}\"", p.display()).as_str())
+            io_failure(std::fs::remove_file(&p), format!("Failed to remove file \"{}\"", p.display()).as_str())?;
         }
     }
     for i in (0..10).rev() { // Rust... just why. Was 10..0 (or 9..-1 I guess) really that syntactically complex?
         let from = fnamep(i);
         let to = fnamep(i + 1);
         if std::path::Path::is_file(&from) {
-            exit_on_error(std::fs::rename(&from, &to), format!("Failed to rename \"{}\"", from.display()).as_str());
+            io_failure(std::fs::rename(&from, &to), format!("Failed to rename \"{}\"", from.display()).as_str())?;
         }
     }
 
-    exit_on_error(std::fs::copy("items.txt", "items00.txt"), "Failed to rename \"items.txt\"");
+    io_failure(std::fs::copy(dir.join("items.txt"), fnamep(0)), "Failed to back up \"items.txt\"")?;
+    Ok(fnamep(0))
 }
 
 
 
-// Generates a vector of strings describing all entries (and also the counter for the counting tags)
-#[cfg(debug_assertions)]
-fn get_formatted_entries(entries: &Vec<Entry>) -> Vec<String> {
-    let counting_tags = vec![
-        "BeerCaseID", "SausagesxID", "milkxID", "sugarID", "yeastID",
-        "potatochipsID", "pizzaxID", "macaronboxxID", "shoppingbagxID",
-        "moosemeatxID", "BoozeID", "pikexID", "juiceconcentrateID",
-        "motoroilID", "brakefluidID", "coolantID", "twostrokeID",
-        "cigarettesID", "sparkplugboxID", "groundcoffeeID", "grillcharcoalID",
-        "lightbulbboxID", "fusepackageID", "r20batteryboxID", "mosquitosprayID"
-    ];
+// Reads the items.txt in the given directory back and compares it with the
+// entries that were saved. If they differ the backup is put back in place.
+fn verify_items_file(dir: &std::path::Path, entries: &[Entry]) -> Result<(), Failure> {
+    let mut expected: Vec<u8> = Vec::new();
+    io_failure(write_entries(&mut expected, entries), "Failed to serialize the entries")?;
+    if try_read_items_file(dir)? != expected {
+        io_failure(std::fs::copy(dir.join("items00.txt"), dir.join("items.txt")), "Failed to restore \"items.txt\"")?;
+        let msg = "\"items.txt\" doesn't contain what was saved, the backup was put back in place".to_string();
+        return Err(Failure { code: ExitCode::ValidationFailed, msg });
+    }
+    Ok(())
+}
+
+
+
+// Backs up the items.txt in the given directory, replaces it with the given
+// entries and makes sure it was written correctly. Returns the backup's path.
+fn try_replace_items_file(dir: &std::path::Path, entries: &[Entry]) -> Result<std::path::PathBuf, Failure> {
+    let backup = backup_items_file(dir)?;
+    try_save_entries(&dir.join("items.txt"), entries)?;
+    verify_items_file(dir, entries)?;
+    Ok(backup)
+}
+
+
+
+// Like 'try_replace_items_file', but quits if it fails
+fn replace_items_file(dir: &std::path::Path, entries: &[Entry]) -> std::path::PathBuf {
+    exit_on_failure(try_replace_items_file(dir, entries))
+}
+
 
+
+// Generates a vector of strings describing all entries (and also the counter for the counting tags)
+#[cfg(any(debug_assertions, test))]
+fn get_formatted_entries(entries: &[Entry]) -> Vec<String> {
     let mut res: Vec<String> = Vec::new();
     for e in entries {
-        if counting_tags.contains(&e.tag.as_str()) {
+        let tag = display_tag(&e.tag);
+        if (is_counter_tag(&e.tag) || looks_like_counter(e)) && e.data.len() >= 9 {
             let mut idx: usize = 5;
-            res.push(format!("{} ({})", e.tag, get_u32_le(&e.data, &mut idx)));
+            res.push(format!("{} ({})", tag, get_u32_le(&e.data, &mut idx)));
         } else {
-            res.push(format!("{}", e.tag));
+            res.push(tag);
         }
     }
     res
@@ -513,28 +1238,112 @@ fn get_formatted_entries(entries: &Vec<Entry>) -> Vec<String> {
 
 // Saves the list of entries to a file
 #[cfg(debug_assertions)]
-fn save_entries_list(entries: &Vec<Entry>) {
-    let fmt = get_formatted_entries(&entries);
+fn save_entries_list(dir: &std::path::Path, entries: &[Entry]) {
+    let fmt = get_formatted_entries(entries);
     let mut out = String::new();
     for e in fmt {
-        out.push_str(format!("{}{}", if out == "" { "" } else { "\n" },  e).as_str());
+        out.push_str(format!("{}{}", if out.is_empty() { "" } else { "\n" },  e).as_str());
     }
-    exit_on_error(std::fs::write("items_list.txt", out), "Failed saving \"items_list.txt\"")
+    exit_on_error(std::fs::write(dir.join("items_list.txt"), out), "Failed saving \"items_list.txt\"")
+}
+
+
+
+// Saves what the cleaning did to a file
+#[cfg(debug_assertions)]
+fn save_report(dir: &std::path::Path, report: &CleanReport) {
+    exit_on_error(std::fs::write(dir.join("items_report.txt"), format_report(report)), "Failed saving \"items_report.txt\"")
 }
 
 
 
 fn main() {
-    let items_file: Vec<u8> = exit_on_error(
-        std::fs::read("items.txt"),
-        "File \"items.txt\" was not found or couldn't be read! Make sure the executable is in the same folder as the file."
-    );
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("generate") => return generator::run(&args[1..]),
+        Some("explain") => return explain::run(&args[1..]),
+        Some("watch") => return watch::run(&args[1..]),
+        Some("reinsert") => return reinsert::run(&args[1..]),
+        Some("spawn") => return spawn::run(&args[1..]),
+        Some("move") => return relocate::run(&args[1..]),
+        Some("catalog") => return print!("{}", format_catalog()),
+        Some("baseline") => return baseline::run(&args[1..]),
+        #[cfg(feature = "bench")]
+        Some("bench") => return bench::run(args.get(1).cloned()),
+        _ => ()
+    }
+
+    // set before parsing, so invalid arguments are reported as JSON as well
+    JSON_OUTPUT.store(args.iter().any(|a| a == "--json"), Ordering::Relaxed);
+    let mut options = cli::parse_options(&args);
+    if options.json && (options.interactive || options.explain) {
+        exit("--json can't be combined with --interactive or --explain");
+    }
+
+    let dir = savedir::choose(options.dir.as_ref());
+    options.config.defaults = baseline::load(&dir);
+    let mut rules = rules::build_rules(&options.config);
+
+    // the game saves when it's closed, so wait for it before reading the file
+    game::ensure_not_running(options.if_running);
+    let items_file: Vec<u8> = read_items_file(&dir);
+
+    let mut entries: Vec<Entry> = generate_entries(&items_file);
+    let entries_before = entries.len();
+    if options.interactive {
+        match interactive::select(&entries, &options.config) {
+            Some(selected) => rules = selected,
+            None => {
+                println!("Nothing was changed");
+                std::process::exit(ExitCode::NothingToDo as i32);
+            }
+        }
+        // the game might have been started in the meantime, waiting for it
+        // doesn't help any more as the file was read already
+        if options.if_running == game::IfRunning::Wait {
+            game::ensure_not_running(game::IfRunning::Refuse);
+        } else {
+            game::ensure_not_running(options.if_running);
+        }
+    }
+
+    let mut report;
+    (entries, report) = clean_save(entries, &options.config, &rules);
+    let code = if changed_anything(&report) { ExitCode::Cleaned } else { ExitCode::NothingToDo };
+    let backup = (code == ExitCode::Cleaned).then(|| {
+        let backup = replace_items_file(&dir, &entries);
+        // the save is cleaned already, a damaged journal mustn't hide that
+        if let Err(f) = journal::record(&dir, &report) {
+            report.warnings.push(format!("the clean couldn't be added to the journal, {}", f.msg));
+        }
+        backup
+    });
+
+    if options.json {
+        println!("{}", json::format_outcome(&json::Outcome {
+            code,
+            entries_before,
+            entries_after: entries.len(),
+            backup: backup.as_deref(),
+            report: &report
+        }));
+    } else {
+        if options.explain {
+            print!("{}", explain::format_explanation(&report, None));
+        }
+        for warning in &report.warnings {
+            println!("Warning: {}", warning);
+        }
+        match code {
+            ExitCode::Cleaned => println!("{}", format_summary(&report)),
+            _ => println!("Nothing to clean, \"items.txt\" was left untouched")
+        }
+    }
 
-    backup_items_file();
-    let mut entries: Vec<Entry> = generate_entries(items_file);
-    entries = clean_entries(entries);
-    save_new_items_file(&entries);
-    
     #[cfg(debug_assertions)]
-    save_entries_list(&entries)
+    {
+        save_entries_list(&dir, &entries);
+        save_report(&dir, &report);
+    }
+    std::process::exit(code as i32);
 }
@@ -0,0 +1,128 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Brings back items that were removed by an earlier clean, see 'journal'
+
+use std::time::{Duration, SystemTime};
+
+use crate::journal::{self, Session};
+use crate::{
+    Entry, cli, counter_tag_of, display_tag, exit, exit_on_failure, game, generate_entries, get_item_id,
+    get_u32_le, is_counter_tag, is_in_landfill, parse_id, parse_tag, read_items_file, replace_items_file,
+    savedir, tag_set_new_count
+};
+
+
+
+// Lists what the cleans in the journal removed, the newest first
+pub fn format_journal(sessions: &[Session]) -> String {
+    if sessions.iter().all(|s| s.removed.is_empty()) {
+        return String::from("There's nothing to bring back\n");
+    }
+    let mut out = String::new();
+    for s in sessions.iter().rev().filter(|s| !s.removed.is_empty()) {
+        let ids: Vec<String> = s.removed_ids().iter().map(|id| display_tag(id)).collect();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(s.time);
+        out.push_str(format!("Cleaned {}, removed {} items:\n", savedir::format_age(time), ids.len()).as_str());
+        for line in ids.chunks(8) {
+            out.push_str(format!("    {}\n", line.join(", ")).as_str());
+        }
+    }
+    out
+}
+
+
+
+// Puts the entries of a removed item back into a save. Items of a group with
+// a counter get the next free ID and the counter is raised to it, the others
+// keep their ID if nothing else took it in the meantime. Returns the new ID.
+pub fn reinsert(entries: &mut Vec<Entry>, removed: &[Entry]) -> Result<Vec<u8>, String> {
+    let id = get_item_id(&removed[0].tag);
+    let Some(counter) = counter_tag_of(&id) else {
+        if entries.iter().any(|e| !is_counter_tag(&e.tag) && get_item_id(&e.tag) == id) {
+            return Err(format!("Can't bring back \"{}\", there's another item with the same ID", display_tag(&id)));
+        }
+        entries.extend(removed.iter().cloned());
+        return Ok(id);
+    };
+
+    // the next free ID is above the highest one in use and above the counter
+    let parts = parse_tag(&id);
+    let highest = entries.iter()
+        .filter(|e| !is_counter_tag(&e.tag))
+        .filter_map(|e| {
+            let p = parse_tag(&e.tag);
+            if p.base == parts.base && p.colour == parts.colour { parse_id(p.id) } else { None }
+        })
+        .max()
+        .unwrap_or(0);
+    let counter_index = entries.iter().position(|e| e.tag == counter.as_bytes());
+    let counter_value = match counter_index {
+        Some(i) if entries[i].data.len() >= 9 => get_u32_le(&entries[i].data, &mut 5) as usize,
+        _ => 0
+    };
+    let newcount = highest.max(counter_value) + 1;
+
+    let mut group: Vec<Entry> = removed.to_vec();
+    for e in &mut group {
+        tag_set_new_count(e, newcount);
+    }
+    let newid = get_item_id(&group[0].tag);
+
+    // the items of a group come right before its counter
+    match counter_index {
+        Some(i) => {
+            if entries[i].data.len() >= 9 {
+                entries[i].data[5..9].copy_from_slice(&(newcount as u32).to_le_bytes());
+            }
+            entries.splice(i..i, group);
+        },
+        None => entries.extend(group)
+    }
+    Ok(newid)
+}
+
+
+
+// The "reinsert" command: brings back the given items, or lists what can be
+// brought back if none are given
+pub fn run(args: &[String]) {
+    let ids: Vec<Vec<u8>> = args.iter()
+        .take_while(|a| !a.starts_with("--"))
+        .map(|a| get_item_id(a.as_bytes()))
+        .collect();
+    let options = cli::parse_options(&args[ids.len()..]);
+    if options.interactive || options.json {
+        exit("\"reinsert\" can't be used with --interactive or --json");
+    }
+    let dir = savedir::choose(options.dir.as_ref());
+    let mut sessions = exit_on_failure(journal::read(&dir));
+    if ids.is_empty() {
+        return print!("{}", format_journal(&sessions));
+    }
+
+    game::ensure_not_running(options.if_running);
+    let mut entries = generate_entries(&read_items_file(&dir));
+    for id in &ids {
+        // take the item from the newest clean that removed it
+        let Some(session) = sessions.iter_mut().rev().find(|s| s.removed.iter().any(|e| get_item_id(&e.tag) == *id)) else {
+            exit(format!("\"{}\" isn't in the journal, run \"reinsert\" without an item to see what is", display_tag(id)).as_str());
+        };
+        let (group, rest): (Vec<Entry>, Vec<Entry>) = std::mem::take(&mut session.removed).into_iter()
+            .partition(|e| get_item_id(&e.tag) == *id);
+        session.removed = rest;
+        match reinsert(&mut entries, &group) {
+            Ok(newid) => println!("Brought back {} as {}", display_tag(id), display_tag(&newid)),
+            Err(msg) => exit(msg.as_str())
+        }
+        if group.iter().any(is_in_landfill) {
+            println!("Note: it's still in the landfill, so the next clean removes it again");
+        }
+    }
+
+    replace_items_file(&dir, &entries);
+    if let Err(f) = journal::write(&dir, &sessions) {
+        println!("Warning: the journal couldn't be updated, {}", f.msg);
+    }
+}
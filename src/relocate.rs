@@ -0,0 +1,138 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// The "move" command: puts selected items at a new position, i.e. to get back
+// items that fell through the floor or ended up in the lake
+
+use std::collections::HashMap;
+
+use crate::rules::parse_zone;
+use crate::select::{Selector, parse_query};
+use crate::{
+    Entry, ExitCode, cli, display_tag, exit, exit_with, flag_value, game, generate_entries,
+    get_item_id, group_entries, is_counter_tag, parse_position, parse_tag, read_items_file,
+    replace_items_file, savedir, set_position, set_rotation
+};
+
+
+
+// The positions for 'n' items around 'center': all on the same spot without
+// a spread, otherwise on a square grid on the ground with 'spread' meters
+// between the items
+pub fn grid_positions(center: [f32; 3], n: usize, spread: f32) -> Vec<[f32; 3]> {
+    let cols = (n as f32).sqrt().ceil().max(1.0) as usize;
+    let rows = n.div_ceil(cols);
+    (0..n)
+        .map(|i| {
+            let col = (i % cols) as f32 - (cols - 1) as f32 / 2.0;
+            let row = (i / cols) as f32 - (rows.max(1) - 1) as f32 / 2.0;
+            [center[0] + col * spread, center[1], center[2] + row * spread]
+        })
+        .collect()
+}
+
+
+
+// Moves every item matched by one of the selectors, optionally rotating it
+// too. Items without a Transform can't be moved. Returns the moved IDs.
+pub fn move_items(
+    entries: &mut [Entry],
+    selectors: &[Selector],
+    to: [f32; 3],
+    rotation: Option<[f32; 4]>,
+    spread: f32
+) -> Vec<Vec<u8>> {
+    let ids: Vec<Vec<u8>> = entries.iter().map(|e| get_item_id(&e.tag)).collect();
+    let selected: Vec<Vec<u8>> = group_entries(entries, &ids).iter()
+        .filter(|g| !is_counter_tag(g.id) && g.property(b"Transform").is_some())
+        .filter(|g| selectors.iter().any(|s| s.matches(g)))
+        .map(|g| g.id.to_vec())
+        .collect();
+
+    let positions: HashMap<&[u8], [f32; 3]> = selected.iter()
+        .map(Vec::as_slice)
+        .zip(grid_positions(to, selected.len(), spread))
+        .collect();
+    for (e, id) in entries.iter_mut().zip(&ids) {
+        let Some(&pos) = positions.get(id.as_slice()) else { continue };
+        if parse_tag(&e.tag).property != b"Transform" {
+            continue;
+        }
+        set_position(&mut e.data, pos);
+        if let Some(rotation) = rotation {
+            set_rotation(&mut e.data, rotation);
+        }
+    }
+    selected
+}
+
+
+
+pub fn run(args: &[String]) {
+    let usage = "Usage: move [<item>...] [--base BASE] [--zone ZONE] [--query QUERY] --to x,y,z \
+                 [--rotation x,y,z,w] [--spread METERS] [--dir DIR] [--wait|--force]";
+    let mut selectors: Vec<Selector> = Vec::new();
+    let mut to: Option<[f32; 3]> = None;
+    let mut rotation: Option<[f32; 4]> = None;
+    let mut spread: f32 = 0.0;
+    let mut rest: Vec<String> = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base" => selectors.push(Selector::Base(flag_value::<String>(arg, args.next()).into_bytes())),
+            "--zone" => {
+                let value: String = flag_value(arg, args.next());
+                match parse_zone(&value) {
+                    Some(zone) => selectors.push(Selector::Zone(zone)),
+                    None => exit(format!("Invalid zone \"{}\", expected a name or \"x1,y1,z1,x2,y2,z2\"", value).as_str())
+                }
+            },
+            "--query" => match parse_query(&flag_value::<String>(arg, args.next())) {
+                Ok(terms) => selectors.push(Selector::Query(terms)),
+                Err(msg) => exit(msg.as_str())
+            },
+            "--to" => {
+                let value: String = flag_value(arg, args.next());
+                to = Some(parse_position(&value).unwrap_or_else(|| exit(format!("Invalid position \"{}\", expected \"x,y,z\"", value).as_str())));
+            },
+            "--rotation" => {
+                let value: String = flag_value(arg, args.next());
+                let q: Option<[f32; 4]> = value.split(',')
+                    .map(|n| n.trim().parse().ok())
+                    .collect::<Option<Vec<f32>>>()
+                    .and_then(|q| q.try_into().ok());
+                rotation = Some(q.unwrap_or_else(|| exit(format!("Invalid rotation \"{}\", expected \"x,y,z,w\"", value).as_str())));
+            },
+            "--spread" => spread = flag_value(arg, args.next()),
+            _ if !arg.starts_with("--") => selectors.push(Selector::Id(arg.clone().into_bytes())),
+            _ => {
+                // the options shared with the other commands, with their values
+                rest.push(arg.clone());
+                if arg == "--dir" {
+                    rest.extend(args.next().cloned());
+                }
+            }
+        }
+    }
+    let to = to.unwrap_or_else(|| exit(usage));
+    if selectors.is_empty() {
+        exit(usage);
+    }
+    let options = cli::parse_options(&rest);
+    if options.interactive || options.json {
+        exit("\"move\" can't be used with --interactive or --json");
+    }
+
+    let dir = savedir::choose(options.dir.as_ref());
+    game::ensure_not_running(options.if_running);
+    let mut entries = generate_entries(&read_items_file(&dir));
+    let moved = move_items(&mut entries, &selectors, to, rotation, spread);
+    if moved.is_empty() {
+        exit_with(ExitCode::NothingToDo, "No items with a position match, nothing was moved");
+    }
+    replace_items_file(&dir, &entries);
+    let moved: Vec<String> = moved.iter().map(|id| display_tag(id)).collect();
+    println!("Moved {} items: {}", moved.len(), moved.join(", "));
+}
@@ -0,0 +1,575 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// The rules deciding which items get removed from a save. Every rule looks at
+// one item (all entries sharing an item ID) at a time and either keeps it,
+// deletes it or protects it. See 'clean_entries' for how the verdicts of all
+// rules are combined.
+
+use std::collections::HashSet;
+
+use crate::bags::{BAG_BASE, CONTENTS_PROPERTY, decode_contents};
+use crate::filter::wildcard_match;
+use crate::transform::TransformAction;
+use crate::world::{WorldAction, WorldBounds};
+use crate::{
+    Category, DEFAULT_ITEMS, Entry, SPRAYCAN_FILL_PROPERTY, decode_position, display_tag, exit, flag_value,
+    get_f32_value, is_counter_tag, is_in_landfill, is_protected, looks_like_counter, parse_position, parse_tag
+};
+
+
+
+// All entries of one item, i.e. "pikex36Transform" and "pikex36Condition"
+pub struct ItemGroup<'a> {
+    pub id: &'a [u8],          // the item ID, "pikex36"
+    pub entries: Vec<&'a Entry>
+}
+
+impl ItemGroup<'_> {
+    // Returns the entry of the item that stores the given property
+    pub fn property(&self, property: &[u8]) -> Option<&Entry> {
+        self.entries.iter().copied().find(|e| parse_tag(&e.tag).property == property)
+    }
+
+    // The position of the item, if it has a Transform
+    pub fn position(&self) -> Option<[f32; 3]> {
+        decode_position(&self.property(b"Transform")?.data)
+    }
+}
+
+
+
+// What a rule wants to happen to an item, with a reason for humans
+pub enum Verdict {
+    Keep,
+    Delete(String),
+    Protect(String)
+}
+
+
+
+pub trait CleanRule {
+    // A short name, i.e. "landfill"
+    fn name(&self) -> &'static str;
+
+    // Looks at one item and decides what should happen to it
+    fn check(&self, group: &ItemGroup) -> Verdict;
+
+    // Rules comparing items with each other (see 'RetentionRule') get all
+    // items no rule deleted or protected and return the IDs of the ones to
+    // delete, with a reason. The other rules don't need this.
+    fn check_all(&self, _kept: &[&ItemGroup]) -> Vec<(Vec<u8>, String)> {
+        Vec::new()
+    }
+
+    // Rules protecting items because of other items (see 'ShoppingBagRule')
+    // get all items before any of them is judged and return the IDs of the
+    // ones to protect, with a reason. The other rules don't need this.
+    fn protect_all(&self, _groups: &[ItemGroup]) -> Vec<(Vec<u8>, String)> {
+        Vec::new()
+    }
+
+    // The IDs renumbering must never hand out, even if their items are gone
+    // (see 'BaselineRule'). Most rules don't reserve any.
+    fn reserved(&self) -> Vec<Vec<u8>> {
+        Vec::new()
+    }
+}
+
+
+
+// Counter tags aren't items, they have to stay no matter what
+pub struct CounterTagRule;
+
+impl CleanRule for CounterTagRule {
+    fn name(&self) -> &'static str { "counter tag" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        if is_counter_tag(group.id) || group.entries.iter().all(|e| looks_like_counter(e)) {
+            Verdict::Protect(String::from("counter tags are never removed"))
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+
+
+// The items present on a fresh save game, see 'DEFAULT_ITEMS'
+pub struct DefaultItemsRule;
+
+impl CleanRule for DefaultItemsRule {
+    fn name(&self) -> &'static str { "default item" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        if DEFAULT_ITEMS.contains(&group.id) {
+            Verdict::Protect(String::from("part of a fresh save game"))
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+
+
+// The items of a fresh save according to the baseline, see 'baseline'
+pub struct BaselineRule {
+    pub defaults: HashSet<Vec<u8>>
+}
+
+impl CleanRule for BaselineRule {
+    fn name(&self) -> &'static str { "baseline" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        if self.defaults.contains(group.id) {
+            Verdict::Protect(String::from("part of the fresh save baseline"))
+        } else {
+            Verdict::Keep
+        }
+    }
+
+    // The defaults are known by their ID only, so a lost default's ID must
+    // never go to another item
+    fn reserved(&self) -> Vec<Vec<u8>> {
+        self.defaults.iter().cloned().collect()
+    }
+}
+
+
+
+// Shopping bags that still hold items, removing them would lose what's inside.
+// Without --shopping-bags the contents aren't updated, so the items in the
+// bags are protected as well to keep their IDs. Empty bags and bags whose
+// contents don't fit the (unverified) layout are treated like any other item.
+// See 'bags'.
+pub struct ShoppingBagRule {
+    pub protect_contents: bool
+}
+
+impl CleanRule for ShoppingBagRule {
+    fn name(&self) -> &'static str { "shopping bag" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        if parse_tag(group.id).base != BAG_BASE {
+            return Verdict::Keep;
+        }
+        let Some(contents) = group.property(CONTENTS_PROPERTY) else { return Verdict::Keep };
+        match decode_contents(&contents.data) {
+            Some(ids) if !ids.is_empty() => Verdict::Protect(format!("still holds {} items", ids.len())),
+            _ => Verdict::Keep
+        }
+    }
+
+    fn protect_all(&self, groups: &[ItemGroup]) -> Vec<(Vec<u8>, String)> {
+        if !self.protect_contents {
+            return Vec::new();
+        }
+        let mut res: Vec<(Vec<u8>, String)> = Vec::new();
+        for group in groups.iter().filter(|g| parse_tag(g.id).base == BAG_BASE) {
+            let Some(contents) = group.property(CONTENTS_PROPERTY) else { continue };
+            for id in decode_contents(&contents.data).unwrap_or_default() {
+                res.push((id, format!("it's in {}", display_tag(group.id))));
+            }
+        }
+        res
+    }
+}
+
+
+
+// Items whose base is marked as protected in the catalog plus the prefixes
+// the user asked to protect
+pub struct ProtectedPrefixRule {
+    pub prefixes: Vec<Vec<u8>>
+}
+
+impl CleanRule for ProtectedPrefixRule {
+    fn name(&self) -> &'static str { "protected" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        if is_protected(group.id) {
+            let base = String::from_utf8_lossy(parse_tag(group.id).base);
+            return Verdict::Protect(format!("\"{}\" can be attached to the car, the house, or the radio", base));
+        }
+        match self.prefixes.iter().find(|p| group.id.starts_with(p)) {
+            Some(p) => Verdict::Protect(format!("starts with \"{}\"", String::from_utf8_lossy(p))),
+            None => Verdict::Keep
+        }
+    }
+}
+
+
+
+// Items in the dedicated landfill spot
+pub struct LandfillRule;
+
+impl CleanRule for LandfillRule {
+    fn name(&self) -> &'static str { "landfill" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        if group.entries.iter().any(|e| is_in_landfill(e)) {
+            Verdict::Delete(String::from("Transform matches the landfill position byte for byte"))
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+
+
+// Spray cans without any paint left, wherever they are. Only used with
+// --empty-spraycans, the fill property (see 'SPRAYCAN_FILL_PROPERTY') hasn't
+// been checked against a real save yet.
+pub struct EmptySpraycanRule;
+
+impl CleanRule for EmptySpraycanRule {
+    fn name(&self) -> &'static str { "empty spray can" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        if parse_tag(group.id).base != b"spraycan" {
+            return Verdict::Keep;
+        }
+        match group.property(SPRAYCAN_FILL_PROPERTY).and_then(get_f32_value) {
+            Some(fill) if fill <= 0.0 => Verdict::Delete(format!("fill value is {}", fill)),
+            _ => Verdict::Keep
+        }
+    }
+}
+
+
+
+// Items whose "Consumed" flag is set
+pub struct ConsumedRule;
+
+impl CleanRule for ConsumedRule {
+    fn name(&self) -> &'static str { "consumed" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        match group.property(b"Consumed").and_then(|e| e.data.get(5)) {
+            Some(&consumed) if consumed != 0 => Verdict::Delete(String::from("Consumed is set")),
+            _ => Verdict::Keep
+        }
+    }
+}
+
+
+
+// Items whose condition dropped below a threshold (i.e. pikes, which don't
+// have a "Consumed" flag)
+pub struct ConditionRule {
+    pub below: f32
+}
+
+impl CleanRule for ConditionRule {
+    fn name(&self) -> &'static str { "condition" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        match group.property(b"Condition").and_then(get_f32_value) {
+            Some(condition) if condition < self.below => {
+                Verdict::Delete(format!("condition {} is below {}", condition, self.below))
+            },
+            _ => Verdict::Keep
+        }
+    }
+}
+
+
+
+// Roughly where the house is, to find the items farthest away from it. It's
+// only a rough guess, --house sets a better one.
+pub const DEFAULT_HOUSE_POS: [f32; 3] = [-10.0, 0.0, 10.0];
+
+
+
+// Keeps at most a number of instances of the matching item bases. The items
+// to remove are picked in this order: consumed ones first, then the ones in
+// the worst condition (or the emptiest spray cans), then the ones farthest
+// from the house. Protected items don't count.
+pub struct RetentionRule {
+    pub caps: Vec<(String, usize)>, // an item base pattern (see 'filter::wildcard_match') and how many to keep
+    pub house: [f32; 3]
+}
+
+impl RetentionRule {
+    // What decides whether an item goes first: consumed, condition and the
+    // distance to the house
+    fn removal_key(&self, group: &ItemGroup) -> (bool, Option<f32>, Option<f32>) {
+        let consumed = group.property(b"Consumed").and_then(|e| e.data.get(5)).is_some_and(|&c| c != 0);
+        let condition = group.property(b"Condition")
+            .or_else(|| group.property(SPRAYCAN_FILL_PROPERTY))
+            .and_then(get_f32_value);
+        let distance = group.position()
+            .map(|p| (0..3).map(|i| (p[i] - self.house[i]).powi(2)).sum::<f32>().sqrt());
+        (consumed, condition, distance)
+    }
+}
+
+impl CleanRule for RetentionRule {
+    fn name(&self) -> &'static str { "retention" }
+
+    fn check(&self, _group: &ItemGroup) -> Verdict {
+        Verdict::Keep
+    }
+
+    fn check_all(&self, kept: &[&ItemGroup]) -> Vec<(Vec<u8>, String)> {
+        // the kept items of every capped base, in the order the bases first appear in
+        let mut bases: Vec<(&[u8], usize, Vec<&ItemGroup>)> = Vec::new();
+        for &group in kept {
+            let base = parse_tag(group.id).base;
+            let Some(&(_, cap)) = self.caps.iter().find(|(p, _)| wildcard_match(p.as_bytes(), base)) else { continue };
+            match bases.iter_mut().find(|(b, _, _)| *b == base) {
+                Some((_, _, items)) => items.push(group),
+                None => bases.push((base, cap, vec![group]))
+            }
+        }
+
+        let mut res: Vec<(Vec<u8>, String)> = Vec::new();
+        for (base, cap, items) in bases {
+            if items.len() <= cap {
+                continue;
+            }
+            let mut items: Vec<_> = items.into_iter().map(|g| (self.removal_key(g), g)).collect();
+            items.sort_by(|((c1, cond1, d1), _), ((c2, cond2, d2), _)| {
+                c2.cmp(c1)
+                    .then(cond1.unwrap_or(f32::INFINITY).total_cmp(&cond2.unwrap_or(f32::INFINITY)))
+                    .then(d2.unwrap_or(0.0).total_cmp(&d1.unwrap_or(0.0)))
+            });
+            let total = items.len();
+            for ((consumed, condition, distance), group) in &items[..total - cap] {
+                let mut why: Vec<String> = Vec::new();
+                if *consumed {
+                    why.push(String::from("consumed"));
+                }
+                if let Some(condition) = condition {
+                    why.push(format!("condition {}", condition));
+                }
+                if let Some(distance) = distance {
+                    why.push(format!("{:.0} m from the house", distance));
+                }
+                res.push((
+                    group.id.to_vec(),
+                    format!("only {} of the {} \"{}\" are kept, this one is {}", cap, total, display_tag(base), why.join(", "))
+                ));
+            }
+        }
+        res
+    }
+}
+
+
+
+// A box on the map, everything between 'min' and 'max' is inside of it
+#[derive(Clone)]
+pub struct Zone {
+    pub name: String,
+    pub min: [f32; 3],
+    pub max: [f32; 3]
+}
+
+impl Zone {
+    pub fn contains(&self, pos: [f32; 3]) -> bool {
+        (0..3).all(|i| self.min[i] <= pos[i] && pos[i] <= self.max[i])
+    }
+}
+
+
+
+// The zones that can be referred to by name. The landfill zone is the area
+// around the landfill position, so items that were dropped next to the exact
+// spot are found too.
+pub fn named_zone(name: &str) -> Option<Zone> {
+    match name {
+        "landfill" => Some(Zone {
+            name: String::from("landfill"),
+            min: [-689.3, -0.5, -737.3],
+            max: [-669.3, 19.5, -717.3]
+        }),
+        _ => None
+    }
+}
+
+
+
+// Parses a zone given on the command line, either by name or as
+// "x1,y1,z1,x2,y2,z2"
+pub fn parse_zone(s: &str) -> Option<Zone> {
+    if let Some(zone) = named_zone(s) {
+        return Some(zone);
+    }
+    let n: Vec<f32> = s.split(',').map(|n| n.trim().parse().ok()).collect::<Option<_>>()?;
+    if n.len() != 6 {
+        return None;
+    }
+    Some(Zone {
+        name: String::from(s),
+        min: [n[0].min(n[3]), n[1].min(n[4]), n[2].min(n[5])],
+        max: [n[0].max(n[3]), n[1].max(n[4]), n[2].max(n[5])]
+    })
+}
+
+
+
+// Items inside of one of the given zones
+pub struct ZoneRule {
+    pub zones: Vec<Zone>
+}
+
+impl CleanRule for ZoneRule {
+    fn name(&self) -> &'static str { "zone" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        let Some(pos) = group.position() else { return Verdict::Keep };
+        match self.zones.iter().find(|z| z.contains(pos)) {
+            Some(z) => Verdict::Delete(format!("inside zone \"{}\"", z.name)),
+            None => Verdict::Keep
+        }
+    }
+}
+
+
+
+// Items that fell out of the world, see 'world'
+pub struct OutOfWorldRule {
+    pub bounds: WorldBounds
+}
+
+impl CleanRule for OutOfWorldRule {
+    fn name(&self) -> &'static str { "out of world" }
+
+    fn check(&self, group: &ItemGroup) -> Verdict {
+        match self.bounds.check_group(group) {
+            Some(reason) => Verdict::Delete(reason),
+            None => Verdict::Keep
+        }
+    }
+}
+
+
+
+// Which rules to run, the default only removes what's in the landfill
+#[derive(Default)]
+pub struct CleanConfig {
+    pub consumed: bool,                          // remove items whose "Consumed" flag is set
+    pub empty_spraycans: bool,                   // remove spray cans without paint left
+    pub shopping_bags: bool,                     // update the contents of shopping bags, see 'bags'
+    pub condition_below: Option<f32>,            // remove items with a condition below this
+    pub zones: Vec<Zone>,                        // remove items inside of these zones
+    pub protect: Vec<String>,                    // never touch items whose ID starts with one of these
+    pub out_of_world: Option<WorldAction>,       // what to do with items that fell out of the world
+    pub world_bounds: Option<Zone>,              // where items have to be, see 'world::default_world_bounds'
+    pub min_height: Option<f32>,                 // how deep items may be, see 'world::DEFAULT_MIN_HEIGHT'
+    pub rescue_point: Option<[f32; 3]>,          // where items that fell out of the world are moved to
+    pub bad_transforms: Option<TransformAction>, // what to do with items whose Transform is corrupted
+    pub only: Vec<String>,                       // only clean the item bases matching one of these, see 'filter'
+    pub exclude: Vec<String>,                    // never clean the item bases matching one of these
+    pub categories: Vec<Category>,               // only clean the items of these categories (as well as the --only ones)
+    pub keep_at_most: Vec<(String, usize)>,      // item base patterns and how many of them to keep at most
+    pub defaults: Vec<Vec<u8>>,                  // the item IDs of the baseline, see 'baseline::load'
+    pub house: Option<[f32; 3]>                  // where the house is, see 'DEFAULT_HOUSE_POS'
+}
+
+impl CleanConfig {
+    // Handles a command line flag belonging to the cleaning rules, returns
+    // false if the flag isn't one of them
+    pub fn parse_flag<'a>(&mut self, flag: &str, args: &mut impl Iterator<Item = &'a String>) -> bool {
+        match flag {
+            "--consumed" => self.consumed = true,
+            "--empty-spraycans" => self.empty_spraycans = true,
+            "--shopping-bags" => self.shopping_bags = true,
+            "--condition-below" => self.condition_below = Some(flag_value(flag, args.next())),
+            "--zone" => {
+                let value: String = flag_value(flag, args.next());
+                match parse_zone(&value) {
+                    Some(zone) => self.zones.push(zone),
+                    None => exit(format!("Invalid zone \"{}\", expected a name or \"x1,y1,z1,x2,y2,z2\"", value).as_str())
+                }
+            },
+            "--protect" => self.protect.push(flag_value(flag, args.next())),
+            "--out-of-world" => self.out_of_world = Some(flag_value(flag, args.next())),
+            "--world-bounds" => {
+                let value: String = flag_value(flag, args.next());
+                match parse_zone(&value) {
+                    Some(zone) => self.world_bounds = Some(zone),
+                    None => exit(format!("Invalid bounds \"{}\", expected \"x1,y1,z1,x2,y2,z2\"", value).as_str())
+                }
+            },
+            "--category" => {
+                let value: String = flag_value(flag, args.next());
+                for name in value.split(',').map(str::trim) {
+                    match name.parse() {
+                        Ok(category) => self.categories.push(category),
+                        Err(_) => exit(format!(
+                            "Unknown category \"{}\", expected food, drinks, car-fluids, car-parts, household or spraycans",
+                            name
+                        ).as_str())
+                    }
+                }
+            },
+            "--only" => self.only.extend(flag_value::<String>(flag, args.next()).split(',').map(String::from)),
+            "--exclude" => self.exclude.extend(flag_value::<String>(flag, args.next()).split(',').map(String::from)),
+            "--keep-at-most" => {
+                let value: String = flag_value(flag, args.next());
+                match value.split_once('=').and_then(|(base, n)| Some((String::from(base), n.trim().parse().ok()?))) {
+                    Some(cap) => self.keep_at_most.push(cap),
+                    None => exit(format!("Invalid cap \"{}\", expected \"BASE=N\"", value).as_str())
+                }
+            },
+            "--house" => {
+                let value: String = flag_value(flag, args.next());
+                match parse_position(&value) {
+                    Some(pos) => self.house = Some(pos),
+                    None => exit(format!("Invalid position \"{}\", expected \"x,y,z\"", value).as_str())
+                }
+            },
+            "--bad-transforms" => self.bad_transforms = Some(flag_value(flag, args.next())),
+            "--min-height" => self.min_height = Some(flag_value(flag, args.next())),
+            "--rescue-point" => {
+                let value: String = flag_value(flag, args.next());
+                match parse_position(&value) {
+                    Some(pos) => self.rescue_point = Some(pos),
+                    None => exit(format!("Invalid position \"{}\", expected \"x,y,z\"", value).as_str())
+                }
+            },
+            _ => return false
+        }
+        true
+    }
+}
+
+
+
+// Puts the rules together. Protecting rules come first, they win over
+// everything else.
+pub fn build_rules(config: &CleanConfig) -> Vec<Box<dyn CleanRule>> {
+    let mut rules: Vec<Box<dyn CleanRule>> = vec![
+        Box::new(CounterTagRule),
+        Box::new(DefaultItemsRule),
+        Box::new(BaselineRule { defaults: config.defaults.iter().cloned().collect() }),
+        Box::new(ShoppingBagRule { protect_contents: !config.shopping_bags }),
+        Box::new(ProtectedPrefixRule { prefixes: config.protect.iter().map(|p| p.clone().into_bytes()).collect() }),
+        Box::new(LandfillRule),
+    ];
+    if config.empty_spraycans {
+        rules.push(Box::new(EmptySpraycanRule));
+    }
+    if config.consumed {
+        rules.push(Box::new(ConsumedRule));
+    }
+    if let Some(below) = config.condition_below {
+        rules.push(Box::new(ConditionRule { below }));
+    }
+    if !config.zones.is_empty() {
+        rules.push(Box::new(ZoneRule { zones: config.zones.clone() }));
+    }
+    if config.out_of_world == Some(WorldAction::Delete) {
+        rules.push(Box::new(OutOfWorldRule { bounds: WorldBounds::new(config) }));
+    }
+    if !config.keep_at_most.is_empty() {
+        rules.push(Box::new(RetentionRule {
+            caps: config.keep_at_most.clone(),
+            house: config.house.unwrap_or(DEFAULT_HOUSE_POS)
+        }));
+    }
+    rules
+}
@@ -0,0 +1,185 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Finds the directory of the save game, so the executable doesn't have to be
+// copied next to it
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{JSON_OUTPUT, exit, info};
+
+
+
+// Steam's ID of My Summer Car, the Proton prefix is named after it
+#[cfg(target_os = "linux")]
+const STEAM_APP_ID: &str = "516750";
+
+
+
+// Where the game keeps its saves, relative to the user's home directory
+// (inside of the Proton prefix on Linux)
+const SAVE_DIR: &str = "AppData/LocalLow/Amistech/My Summer Car";
+
+
+
+// Reads the library paths from the contents of Steam's libraryfolders.vdf:
+//     "libraryfolders"
+//     {
+//         "0"
+//         {
+//             "path"      "/home/user/.local/share/Steam"
+//             ...
+// Backslashes in the strings are escaped, i.e. "D:\\SteamLibrary".
+pub fn parse_library_folders(vdf: &str) -> Vec<PathBuf> {
+    let mut res: Vec<PathBuf> = Vec::new();
+    for line in vdf.lines() {
+        // collect the quoted strings of the line
+        let mut strings: Vec<String> = Vec::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c != '"' {
+                continue;
+            }
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => s.extend(chars.next()),
+                    _ => s.push(c)
+                }
+            }
+            strings.push(s);
+        }
+        if let [key, value] = strings.as_slice() {
+            if key.eq_ignore_ascii_case("path") {
+                res.push(PathBuf::from(value));
+            }
+        }
+    }
+    res
+}
+
+
+
+// Every Steam library that might have the game installed: the Steam
+// installations themselves and the libraries they know of
+#[cfg(target_os = "linux")]
+fn steam_libraries(home: &Path) -> Vec<PathBuf> {
+    let roots = [
+        home.join(".steam/steam"),
+        home.join(".local/share/Steam"),
+        home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam") // Flatpak
+    ];
+    let mut res: Vec<PathBuf> = Vec::new();
+    for root in roots.iter().filter(|r| r.is_dir()) {
+        res.push(root.clone());
+        if let Ok(vdf) = std::fs::read_to_string(root.join("steamapps/libraryfolders.vdf")) {
+            res.extend(parse_library_folders(&vdf));
+        }
+    }
+    res
+}
+
+
+
+// The directories that might hold the save, whether they exist or not
+pub fn possible_save_dirs(home: &Path) -> Vec<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        steam_libraries(home).iter()
+            .map(|lib| {
+                lib.join("steamapps/compatdata").join(STEAM_APP_ID)
+                    .join("pfx/drive_c/users/steamuser")
+                    .join(SAVE_DIR)
+            })
+            .collect()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        vec![home.join(SAVE_DIR)]
+    }
+}
+
+
+
+// The directories that actually hold an items.txt, without duplicates (i.e.
+// ~/.steam/steam is usually a link to ~/.local/share/Steam), the one saved
+// last first
+pub fn find_save_dirs(home: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let mut res: Vec<(PathBuf, SystemTime)> = Vec::new();
+    for dir in possible_save_dirs(home) {
+        let Ok(dir) = dir.canonicalize() else { continue };
+        let Ok(modified) = dir.join("items.txt").metadata().and_then(|m| m.modified()) else { continue };
+        if !res.iter().any(|(d, _)| *d == dir) {
+            res.push((dir, modified));
+        }
+    }
+    res.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    res
+}
+
+
+
+// Tells how long ago something happened, i.e. "3 hours ago"
+pub fn format_age(time: SystemTime) -> String {
+    let secs = SystemTime::now().duration_since(time).map_or(0, |d| d.as_secs());
+    let (n, unit) = match secs {
+        0..60 => return String::from("just now"),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day")
+    };
+    format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" })
+}
+
+
+
+// Decides which directory to work in: the one given with --dir, the current
+// one if it holds an items.txt, or else the save game found on this computer.
+// Asks if there's more than one.
+pub fn choose(dir: Option<&PathBuf>) -> PathBuf {
+    if let Some(dir) = dir {
+        return dir.clone();
+    }
+    if Path::new("items.txt").is_file() {
+        return PathBuf::from(".");
+    }
+
+    let home = std::env::var_os(if cfg!(target_os = "windows") { "USERPROFILE" } else { "HOME" });
+    let found = home.map(|h| find_save_dirs(Path::new(&h))).unwrap_or_default();
+    match found.len() {
+        0 => exit(
+            "Couldn't find the save game! Either run this in the directory containing \"items.txt\" or pass \
+             the directory with --dir."
+        ),
+        1 => {
+            info(format!("Using the save game in \"{}\"", found[0].0.display()).as_str());
+            return found.into_iter().next().unwrap().0;
+        },
+        _ => ()
+    }
+
+    if JSON_OUTPUT.load(std::sync::atomic::Ordering::Relaxed) {
+        exit("Found more than one save game, pass the one to clean with --dir");
+    }
+    println!("Found more than one save game:");
+    for (i, (dir, modified)) in found.iter().enumerate() {
+        println!("    {}: {} (saved {})", i + 1, dir.display(), format_age(*modified));
+    }
+    loop {
+        print!("Which one should be cleaned? [1] ");
+        std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line).unwrap_or(0) == 0 {
+            exit("No save game chosen");
+        }
+        let line = line.trim();
+        match if line.is_empty() { Ok(1) } else { line.parse::<usize>() } {
+            Ok(n) if (1..=found.len()).contains(&n) => return found[n - 1].0.clone(),
+            _ => println!("Please enter a number from 1 to {}", found.len())
+        }
+    }
+}
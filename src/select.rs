@@ -0,0 +1,134 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Picks items by ID, base, zone or a small query, for the commands that
+// change selected items instead of cleaning the whole save
+
+use crate::rules::{ItemGroup, Zone};
+use crate::{SPRAYCAN_FILL_PROPERTY, get_f32_value, get_item_id, parse_tag};
+
+
+
+// A value of an item a query can look at
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Field {
+    X,
+    Y,
+    Z,
+    Condition,
+    Fill // how full a spray can is
+}
+
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Op {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Equal
+}
+
+
+
+// One condition of a query, all of them have to match
+#[derive(PartialEq, Debug)]
+pub enum Term {
+    Base(Vec<u8>),         // "base=pikex"
+    Consumed(bool),        // "consumed" or "!consumed"
+    Compare(Field, Op, f32) // "y<-10", "condition<=0.5", ...
+}
+
+
+
+// Parses a query like "base=beercase,condition<0.2" or "y<-10"
+pub fn parse_query(s: &str) -> Result<Vec<Term>, String> {
+    let mut res: Vec<Term> = Vec::new();
+    for term in s.split(',').map(str::trim) {
+        match term {
+            "consumed" => res.push(Term::Consumed(true)),
+            "!consumed" => res.push(Term::Consumed(false)),
+            _ => {
+                // the two character operators have to be tried first
+                let ops = [("<=", Op::LessEq), (">=", Op::GreaterEq), ("<", Op::Less), (">", Op::Greater), ("=", Op::Equal)];
+                let Some((field, op, value)) = ops.iter()
+                    .find_map(|(s, op)| term.split_once(s).map(|(f, v)| (f.trim(), *op, v.trim())))
+                else {
+                    return Err(format!("Invalid query term \"{}\"", term));
+                };
+                let field = match field {
+                    "base" if op == Op::Equal => {
+                        res.push(Term::Base(value.as_bytes().to_vec()));
+                        continue;
+                    },
+                    "x" => Field::X,
+                    "y" => Field::Y,
+                    "z" => Field::Z,
+                    "condition" => Field::Condition,
+                    "fill" => Field::Fill,
+                    _ => return Err(format!("Unknown field in query term \"{}\"", term))
+                };
+                let value: f32 = value.parse().map_err(|_| format!("Invalid number in query term \"{}\"", term))?;
+                res.push(Term::Compare(field, op, value));
+            }
+        }
+    }
+    Ok(res)
+}
+
+
+
+// Reads a value of an item, None if the item doesn't have it
+fn field_value(group: &ItemGroup, field: Field) -> Option<f32> {
+    match field {
+        Field::X => group.position().map(|p| p[0]),
+        Field::Y => group.position().map(|p| p[1]),
+        Field::Z => group.position().map(|p| p[2]),
+        Field::Condition => group.property(b"Condition").and_then(get_f32_value),
+        Field::Fill => group.property(SPRAYCAN_FILL_PROPERTY).and_then(get_f32_value)
+    }
+}
+
+
+
+fn term_matches(term: &Term, group: &ItemGroup) -> bool {
+    match term {
+        Term::Base(base) => parse_tag(group.id).base == base.as_slice(),
+        Term::Consumed(consumed) => {
+            group.property(b"Consumed").and_then(|e| e.data.get(5)).is_some_and(|&c| (c != 0) == *consumed)
+        },
+        Term::Compare(field, op, value) => match field_value(group, *field) {
+            Some(v) => match op {
+                Op::Less => v < *value,
+                Op::LessEq => v <= *value,
+                Op::Greater => v > *value,
+                Op::GreaterEq => v >= *value,
+                Op::Equal => v == *value
+            },
+            None => false
+        }
+    }
+}
+
+
+
+// One way of picking items, an item is selected if any selector matches it
+pub enum Selector {
+    Id(Vec<u8>),     // an item ID or a full tag, "pikex3" or "pikex3Transform"
+    Base(Vec<u8>),   // every item of a kind, "pikex"
+    Zone(Zone),      // every item inside a zone
+    Query(Vec<Term>) // every item matching all terms
+}
+
+impl Selector {
+    pub fn matches(&self, group: &ItemGroup) -> bool {
+        match self {
+            Selector::Id(id) => group.id == get_item_id(id).as_slice(),
+            Selector::Base(base) => parse_tag(group.id).base == base.as_slice(),
+            Selector::Zone(zone) => group.position().is_some_and(|p| zone.contains(p)),
+            Selector::Query(terms) => terms.iter().all(|t| term_matches(t, group))
+        }
+    }
+}
@@ -0,0 +1,147 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Puts new items into a save, either copies of an item that's already in it
+// or made from a template. Handy for testing and for replacing shopping that
+// got lost.
+
+use crate::bags::BAG_BASE;
+use crate::generator::{bool_data, float_data, transform_data};
+use crate::{
+    Entry, SPRAYCAN_FILL_PROPERTY, cli, counter_tag_of, display_tag, exit, flag_value, game,
+    generate_entries, get_item_id, is_counter_tag, parse_position, parse_tag, read_items_file,
+    reinsert, replace_items_file, savedir, set_position
+};
+
+
+
+// How far above each other several spawned items are put, so they don't
+// spawn inside of each other
+const STACK_HEIGHT: f32 = 0.5;
+
+
+
+// Makes a new item from scratch: a Transform and, depending on the kind of
+// item, the condition and whether it was consumed or how full it is. The value
+// headers are copied from an entry of the save storing the same property, the
+// generator's headers are made up, so without such an entry it fails.
+fn template(entries: &[Entry], prefix: &[u8], coloured: bool) -> Result<Vec<Entry>, String> {
+    let properties: &[(&[u8], Vec<u8>)] = if coloured {
+        &[(b"Transform", transform_data([0.0; 3])), (SPRAYCAN_FILL_PROPERTY, float_data(1.0))]
+    } else {
+        &[(b"Transform", transform_data([0.0; 3])), (b"Condition", float_data(1.0)), (b"Consumed", bool_data(false))]
+    };
+    properties.iter()
+        .map(|(property, data)| {
+            let mut data = data.clone();
+            let existing = entries.iter()
+                .find(|e| !is_counter_tag(&e.tag) && parse_tag(&e.tag).property == *property && e.data.len() == data.len())
+                .ok_or_else(|| format!(
+                    "Can't spawn \"{}\", there's none in the save to copy and no \"{}\" value of another item to copy the format from",
+                    display_tag(prefix),
+                    display_tag(property)
+                ))?;
+            data[..5].copy_from_slice(&existing.data[..5]);
+            Ok(Entry { tag: [prefix, b"1", property].concat(), data })
+        })
+        .collect()
+}
+
+
+
+// Resets a copied entry to a fresh item at the given position
+fn reset(e: &mut Entry, pos: [f32; 3]) {
+    let parts = parse_tag(&e.tag);
+    if parts.property == b"Transform" {
+        set_position(&mut e.data, pos);
+    } else if (parts.property == b"Condition" || parts.property == SPRAYCAN_FILL_PROPERTY) && e.data.len() >= 9 {
+        e.data[5..9].copy_from_slice(&1.0f32.to_le_bytes());
+    } else if parts.property == b"Consumed" && e.data.len() >= 6 {
+        e.data[5] = 0;
+    }
+}
+
+
+
+// Adds 'count' new items of the given base (with the colour for spray cans,
+// i.e. "spraycan03") at the given position. They're copies of the newest item
+// of the kind in the save, or made from a template if there is none. Returns
+// the IDs of the new items.
+pub fn spawn(entries: &mut Vec<Entry>, prefix: &[u8], count: usize, at: [f32; 3]) -> Result<Vec<Vec<u8>>, String> {
+    let parts_of = |tag: &[u8]| {
+        let parts = parse_tag(tag);
+        [parts.base, parts.colour].concat()
+    };
+    let probe = [prefix, b"1"].concat();
+    if counter_tag_of(&probe).is_none() || parts_of(&probe) != prefix {
+        return Err(format!(
+            "Can't spawn \"{}\", only items with a counter can be spawned (spray cans need their colour, i.e. \"spraycan03\")",
+            display_tag(prefix)
+        ));
+    }
+    // a copy would hold the same items as the original bag
+    if parse_tag(&probe).base == BAG_BASE {
+        return Err("Shopping bags can't be spawned".to_string());
+    }
+
+    // the entries of the last item of the kind in the save
+    let last_id = entries.iter()
+        .rev()
+        .find(|e| !is_counter_tag(&e.tag) && parts_of(&e.tag) == prefix && !parse_tag(&e.tag).id.is_empty())
+        .map(|e| get_item_id(&e.tag));
+    let original: Vec<Entry> = match last_id {
+        Some(id) => entries.iter().filter(|e| get_item_id(&e.tag) == id).cloned().collect(),
+        None => template(entries, prefix, !parse_tag(&probe).colour.is_empty())?
+    };
+
+    let mut res: Vec<Vec<u8>> = Vec::new();
+    for i in 0..count {
+        let mut group = original.clone();
+        for e in &mut group {
+            reset(e, [at[0], at[1] + i as f32 * STACK_HEIGHT, at[2]]);
+        }
+        res.push(reinsert::reinsert(entries, &group)?);
+    }
+    Ok(res)
+}
+
+
+
+// The "spawn" command
+pub fn run(args: &[String]) {
+    let usage = "Usage: spawn <base> --at x,y,z [--count N] [--dir DIR] [--wait|--force]";
+    let Some(prefix) = args.first().filter(|a| !a.starts_with("--")) else { exit(usage) };
+    let mut count: usize = 1;
+    let mut at: Option<[f32; 3]> = None;
+    let mut rest: Vec<String> = Vec::new();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" => count = flag_value(arg, args.next()),
+            "--at" => {
+                let value: String = flag_value(arg, args.next());
+                at = Some(parse_position(&value).unwrap_or_else(|| exit(format!("Invalid position \"{}\", expected \"x,y,z\"", value).as_str())));
+            },
+            _ => rest.push(arg.clone())
+        }
+    }
+    let at = at.unwrap_or_else(|| exit(usage));
+    let options = cli::parse_options(&rest);
+    if options.interactive || options.json {
+        exit("\"spawn\" can't be used with --interactive or --json");
+    }
+
+    let dir = savedir::choose(options.dir.as_ref());
+    game::ensure_not_running(options.if_running);
+    let mut entries = generate_entries(&read_items_file(&dir));
+    match spawn(&mut entries, prefix.as_bytes(), count, at) {
+        Ok(ids) => {
+            replace_items_file(&dir, &entries);
+            let ids: Vec<String> = ids.iter().map(|id| display_tag(id)).collect();
+            println!("Spawned {}", ids.join(", "));
+        },
+        Err(msg) => exit(msg.as_str())
+    }
+}
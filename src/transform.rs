@@ -0,0 +1,160 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Checks the whole Transform of items, not only the position: a rotation
+// that isn't a unit quaternion, values that aren't numbers or are denormal
+// and scales other than 1.0 make items jitter or fly around in the game
+
+use std::collections::HashSet;
+
+use crate::rules::CleanConfig;
+use crate::{Entry, display_tag, get_item_id, is_counter_tag, parse_tag};
+
+
+
+// What happens to items with a corrupted Transform
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransformAction {
+    Report, // only tell about them
+    Fix     // normalise the rotation and reset the scale
+}
+
+impl std::str::FromStr for TransformAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<TransformAction, ()> {
+        match s {
+            "report" => Ok(TransformAction::Report),
+            "fix" => Ok(TransformAction::Fix),
+            _ => Err(())
+        }
+    }
+}
+
+
+
+// How far the length of the rotation and the scale may be off from 1.0. The
+// game doesn't store them exactly, the scale in the example of
+// 'is_in_landfill' is 1.0000001 (01 00 80 3F).
+const TOLERANCE: f32 = 1e-3;
+
+
+
+// The decoded data of a Transform entry, see 'is_in_landfill' for an example
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub position: [f32; 3],
+    pub rotation: [f32; 4], // a quaternion, x, y, z, w
+    pub scale: [f32; 3]
+}
+
+impl Transform {
+    // Reads the position (bytes 6 to 17), the rotation (18 to 33) and the
+    // scale (34 to 45), None if the data is too short
+    pub fn decode(data: &[u8]) -> Option<Transform> {
+        let bytes = data.get(6..46)?;
+        let f = |i: usize| f32::from_le_bytes([bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2], bytes[i * 4 + 3]]);
+        Some(Transform {
+            position: [f(0), f(1), f(2)],
+            rotation: [f(3), f(4), f(5), f(6)],
+            scale: [f(7), f(8), f(9)]
+        })
+    }
+
+    // Writes it back into the data it was decoded from
+    pub fn encode(&self, data: &mut [u8]) {
+        let values = self.position.iter().chain(&self.rotation).chain(&self.scale);
+        for (i, f) in values.enumerate() {
+            data[6 + i * 4..10 + i * 4].copy_from_slice(&f.to_le_bytes());
+        }
+    }
+
+    // Everything that's wrong with it, empty if nothing is
+    pub fn problems(&self) -> Vec<String> {
+        let mut res: Vec<String> = Vec::new();
+        let parts: [(&str, &[f32]); 3] = [("position", &self.position), ("rotation", &self.rotation), ("scale", &self.scale)];
+        for (name, values) in parts {
+            if values.iter().any(|f| !f.is_finite()) {
+                res.push(format!("the {} isn't a valid number", name));
+            } else if values.iter().any(|f| f.is_subnormal()) {
+                res.push(format!("the {} has denormal values", name));
+            }
+        }
+        let length = self.rotation.iter().map(|f| f * f).sum::<f32>().sqrt();
+        if length.is_finite() && (length - 1.0).abs() > TOLERANCE {
+            res.push(format!("the rotation isn't a unit quaternion (length {})", length));
+        }
+        if self.scale.iter().all(|f| f.is_finite()) && self.scale.iter().any(|f| (f - 1.0).abs() > TOLERANCE) {
+            res.push(format!("the scale is {},{},{} instead of 1", self.scale[0], self.scale[1], self.scale[2]));
+        }
+        res
+    }
+
+    // Repairs what can be repaired: denormal values become 0, the rotation is
+    // normalised (or reset if it's no rotation at all) and the scale is reset
+    // to 1. A position that isn't a number can't be guessed, that's what
+    // --out-of-world is for.
+    pub fn fix(&mut self) {
+        for f in self.position.iter_mut().chain(&mut self.rotation).chain(&mut self.scale) {
+            if f.is_subnormal() {
+                *f = 0.0;
+            }
+        }
+        let length = self.rotation.iter().map(|f| f * f).sum::<f32>().sqrt();
+        if !length.is_finite() || length < TOLERANCE {
+            self.rotation = [0.0, 0.0, 0.0, 1.0];
+        } else if (length - 1.0).abs() > TOLERANCE {
+            self.rotation = self.rotation.map(|f| f / length);
+        }
+        if self.scale.iter().any(|f| !f.is_finite() || (f - 1.0).abs() > TOLERANCE) {
+            self.scale = [1.0; 3];
+        }
+    }
+}
+
+
+
+// Checks the Transform of every item if it's configured, protected items are
+// never repaired. Returns the repaired items and the warnings about the
+// corrupted ones that are only reported, protected or can't be repaired.
+pub fn check_transforms(entries: &mut [Entry], config: &CleanConfig, protected: &HashSet<Vec<u8>>) -> (Vec<Vec<u8>>, Vec<String>) {
+    let Some(action) = config.bad_transforms else { return (Vec::new(), Vec::new()) };
+    let mut repaired: Vec<Vec<u8>> = Vec::new();
+    let mut warnings: Vec<String> = Vec::new();
+
+    for e in entries.iter_mut() {
+        if is_counter_tag(&e.tag) || parse_tag(&e.tag).property != b"Transform" {
+            continue;
+        }
+        let Some(mut transform) = Transform::decode(&e.data) else { continue };
+        let problems = transform.problems();
+        if problems.is_empty() {
+            continue;
+        }
+        let id = get_item_id(&e.tag);
+        if action == TransformAction::Report {
+            warnings.push(format!("{} has a corrupted Transform, {}", display_tag(&id), problems.join(", ")));
+            continue;
+        }
+        if protected.contains(&id) {
+            warnings.push(format!(
+                "{} has a corrupted Transform, {}, but it's protected and wasn't repaired",
+                display_tag(&id),
+                problems.join(", ")
+            ));
+            continue;
+        }
+
+        transform.fix();
+        transform.encode(&mut e.data);
+        let left = transform.problems();
+        if left.len() < problems.len() {
+            repaired.push(id.clone());
+        }
+        if !left.is_empty() {
+            warnings.push(format!("{} still has a corrupted Transform, {}", display_tag(&id), left.join(", ")));
+        }
+    }
+    (repaired, warnings)
+}
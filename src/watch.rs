@@ -0,0 +1,109 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Keeps running next to the game and cleans the save every time the game was
+// played, so the landfill never gets the chance to pile up
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::game::{self, IfRunning};
+use crate::rules::{CleanConfig, CleanRule};
+use crate::{
+    Failure, baseline, changed_anything, clean_save, cli, exit, format_summary, journal, parse_entries,
+    rules, savedir, try_read_items_file, try_replace_items_file
+};
+
+
+
+// How often the save is looked at
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+
+
+// When the file was written last, None if it doesn't exist (yet)
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+
+
+// The time of day (UTC) for the log, i.e. "18:42:07"
+fn format_time(time: SystemTime) -> String {
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs()) % 86400;
+    format!("{:0>2}:{:0>2}:{:0>2}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+
+
+// Cleans the save in the given directory once, returns what to log and the
+// warnings. Once the save is written the clean counts as done, even if the
+// journal can't be updated.
+fn clean_once(dir: &Path, config: &CleanConfig, rules: &[Box<dyn CleanRule>]) -> Result<(String, Vec<String>), Failure> {
+    let (entries, mut report) = clean_save(parse_entries(&try_read_items_file(dir)?)?, config, rules);
+    if !changed_anything(&report) {
+        return Ok(("Nothing to clean".to_string(), report.warnings));
+    }
+    try_replace_items_file(dir, &entries)?;
+    if let Err(f) = journal::record(dir, &report) {
+        report.warnings.push(format!("the clean couldn't be added to the journal, {}", f.msg));
+    }
+    Ok((format_summary(&report), report.warnings))
+}
+
+
+
+// The "watch" command: waits for the game to save, then for it to be closed
+// and cleans the save afterwards. Runs until it's killed, even if a clean
+// fails.
+pub fn run(args: &[String]) {
+    let mut options = cli::parse_options(args);
+    if options.interactive || options.json {
+        exit("\"watch\" can't be used with --interactive or --json");
+    }
+    let dir = savedir::choose(options.dir.as_ref());
+    options.config.defaults = baseline::load(&dir);
+    let rules = rules::build_rules(&options.config);
+    let path = dir.join("items.txt");
+
+    println!("Watching \"{}\", press Ctrl+C to stop", path.display());
+    let mut last = modified(&path);
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if modified(&path) == last {
+            continue;
+        }
+
+        // the game writes the save again when it's closed, wait for that
+        println!("[{}] The game saved", format_time(SystemTime::now()));
+        game::ensure_not_running(IfRunning::Wait);
+        // ... and for the file to stop changing, in case it's still being written
+        let mut current = modified(&path);
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let now = modified(&path);
+            if now == current {
+                break;
+            }
+            current = now;
+        }
+        if current.is_none() {
+            println!("[{}] \"{}\" is gone, waiting for it to come back", format_time(SystemTime::now()), path.display());
+            last = None;
+            continue;
+        }
+
+        // a failed clean is tried again the next time the game saves
+        match clean_once(&dir, &options.config, &rules) {
+            Ok((msg, warnings)) => {
+                for warning in warnings {
+                    println!("[{}] Warning: {}", format_time(SystemTime::now()), warning);
+                }
+                println!("[{}] {}", format_time(SystemTime::now()), msg);
+            },
+            Err(f) => println!("[{}] Cleaning failed: {}", format_time(SystemTime::now()), f.msg)
+        }
+        last = modified(&path);
+    }
+}
@@ -0,0 +1,140 @@
+/*********************************************
+* See LICENSE file for licensing information *
+*********************************************/
+
+// Finds items that fell out of the world: physics glitches leave them far
+// outside of the map, deep below the ground or at positions that aren't even
+// numbers, and the game keeps simulating them forever
+
+use std::collections::{HashMap, HashSet};
+
+use crate::relocate::grid_positions;
+use crate::rules::{CleanConfig, ItemGroup, Zone};
+use crate::{Entry, display_tag, get_item_id, group_entries, is_counter_tag, parse_tag, set_position};
+
+
+
+// What happens to items that fell out of the world
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WorldAction {
+    Delete, // remove them like any other rule would
+    Move,   // put them at the rescue point
+    Report  // only tell about them
+}
+
+impl std::str::FromStr for WorldAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<WorldAction, ()> {
+        match s {
+            "delete" => Ok(WorldAction::Delete),
+            "move" => Ok(WorldAction::Move),
+            "report" => Ok(WorldAction::Report),
+            _ => Err(())
+        }
+    }
+}
+
+
+
+// How far apart rescued items are put, so they don't explode out of each other
+const RESCUE_SPREAD: f32 = 1.0;
+
+
+
+// Roughly the map plus a good margin, everything outside of it surely fell out
+// of the world. The height is limited by 'min_height' separately.
+pub fn default_world_bounds() -> Zone {
+    Zone {
+        name: String::from("world"),
+        min: [-3000.0, f32::NEG_INFINITY, -3000.0],
+        max: [3000.0, 1000.0, 3000.0]
+    }
+}
+
+// Nothing on the map is this deep, not even the bottom of the lake
+pub const DEFAULT_MIN_HEIGHT: f32 = -100.0;
+
+
+
+// The limits items have to stay within
+pub struct WorldBounds {
+    pub bounds: Zone,
+    pub min_height: f32
+}
+
+impl WorldBounds {
+    pub fn new(config: &CleanConfig) -> WorldBounds {
+        WorldBounds {
+            bounds: config.world_bounds.clone().unwrap_or_else(default_world_bounds),
+            min_height: config.min_height.unwrap_or(DEFAULT_MIN_HEIGHT)
+        }
+    }
+
+    // Tells why an item at the given position fell out of the world, None if
+    // it didn't
+    pub fn check(&self, pos: [f32; 3]) -> Option<String> {
+        if pos.iter().any(|f| !f.is_finite()) {
+            Some(String::from("the position isn't a valid number"))
+        } else if pos[1] < self.min_height {
+            Some(format!("it's below the minimum height of {}", self.min_height))
+        } else if !self.bounds.contains(pos) {
+            Some(format!("it's outside of \"{}\"", self.bounds.name))
+        } else {
+            None
+        }
+    }
+
+    // Same as 'check' for an item, items without a position never fell anywhere
+    pub fn check_group(&self, group: &ItemGroup) -> Option<String> {
+        if is_counter_tag(group.id) {
+            return None;
+        }
+        self.check(group.position()?)
+    }
+}
+
+
+
+// Handles the items that fell out of the world if it's configured to move or
+// report them (deleting is done by 'rules::OutOfWorldRule'). Protected items
+// are never moved. Returns the moved items and the warnings about the
+// reported and the protected ones.
+pub fn rescue(entries: &mut [Entry], config: &CleanConfig, protected: &HashSet<Vec<u8>>) -> (Vec<Vec<u8>>, Vec<String>) {
+    let action = match config.out_of_world {
+        Some(WorldAction::Delete) | None => return (Vec::new(), Vec::new()),
+        Some(action) => action
+    };
+    let bounds = WorldBounds::new(config);
+
+    let ids: Vec<Vec<u8>> = entries.iter().map(|e| get_item_id(&e.tag)).collect();
+    let lost: Vec<(Vec<u8>, String)> = group_entries(entries, &ids).iter()
+        .filter_map(|g| Some((g.id.to_vec(), bounds.check_group(g)?)))
+        .collect();
+
+    if action == WorldAction::Report {
+        let warnings = lost.into_iter()
+            .map(|(id, reason)| format!("{} fell out of the world, {}", display_tag(&id), reason))
+            .collect();
+        return (Vec::new(), warnings);
+    }
+    let (lost, stay): (Vec<_>, Vec<_>) = lost.into_iter()
+        .partition(|(id, _)| !protected.contains(id));
+    let warnings = stay.into_iter()
+        .map(|(id, reason)| format!("{} fell out of the world, {}, but it's protected and wasn't moved", display_tag(&id), reason))
+        .collect();
+
+    let rescue_point = config.rescue_point.unwrap_or([0.0; 3]);
+    let positions: HashMap<&[u8], [f32; 3]> = lost.iter()
+        .map(|(id, _)| id.as_slice())
+        .zip(grid_positions(rescue_point, lost.len(), RESCUE_SPREAD))
+        .collect();
+    for (e, id) in entries.iter_mut().zip(&ids) {
+        if let Some(&pos) = positions.get(id.as_slice()) {
+            if parse_tag(&e.tag).property == b"Transform" {
+                set_position(&mut e.data, pos);
+            }
+        }
+    }
+    (lost.into_iter().map(|(id, _)| id).collect(), warnings)
+}